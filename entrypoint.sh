@@ -1,12 +1,13 @@
 #!/bin/sh
 set -e
 
-# Create the database and run all migration SQL files
-if [ ! -f /app/tasks.db ]; then
-  for f in /app/migrations/*.sql; do
-    echo "Running migration: $f"
-    sqlite3 /app/tasks.db < "$f"
-  done
+# Maintenance commands pass straight through, e.g.
+#   docker compose exec app /entrypoint.sh create-user bob
+#   docker compose exec app /entrypoint.sh backup /app/backups/tasks-$(date +%F).db
+if [ "$#" -gt 0 ]; then
+    exec /app/eisenpower-rs "$@"
 fi
 
-exec /app/eisenpower-rs
+# Creates /app/tasks.db if needed and applies migrations before serving
+/app/eisenpower-rs migrate
+exec /app/eisenpower-rs serve
@@ -0,0 +1,107 @@
+-- The schema the SQLite migrations build up to (migrations/sqlite, through
+-- 011_incoming_webhooks.sql), in one step. New changes get a migration here
+-- as well as in migrations/sqlite.
+
+CREATE TABLE IF NOT EXISTS boards (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_boards_owner ON boards(owner);
+
+CREATE TABLE IF NOT EXISTS tasks (
+    id BIGSERIAL PRIMARY KEY,
+    title TEXT NOT NULL,
+    task_type TEXT NOT NULL CHECK(task_type IN ('UrgentImportant','UrgentNotImportant','NotUrgentImportant','NotUrgentNotImportant')),
+    bucket TEXT NOT NULL CHECK(bucket IN ('UrgentImportant','UrgentNotImportant','NotUrgentImportant','NotUrgentNotImportant','Today')),
+    completed BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Compared byte by byte, like SQLite does (see src/sort_key.rs)
+    sort_key TEXT COLLATE "C" NOT NULL DEFAULT '',
+    board_id BIGINT REFERENCES boards(id),
+    created_by TEXT,
+    updated_by TEXT,
+    assignee TEXT,
+    version BIGINT NOT NULL DEFAULT 1,
+    due_date DATE,
+    notes TEXT,
+    external_id TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_board_bucket_sort_key ON tasks(board_id, bucket, sort_key);
+CREATE INDEX IF NOT EXISTS idx_tasks_board_assignee ON tasks(board_id, assignee);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tasks_board_external_id ON tasks(board_id, external_id) WHERE external_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS users (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS board_members (
+    board_id BIGINT NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    role TEXT NOT NULL CHECK(role IN ('viewer','editor')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (board_id, username)
+);
+
+CREATE INDEX IF NOT EXISTS idx_board_members_username ON board_members(username);
+
+CREATE TABLE IF NOT EXISTS task_tags (
+    task_id BIGINT NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (task_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_tags_tag ON task_tags(tag);
+
+CREATE TABLE IF NOT EXISTS mail_inboxes (
+    username TEXT PRIMARY KEY,
+    secret TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS webhooks (
+    id BIGSERIAL PRIMARY KEY,
+    board_id BIGINT NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    events TEXT NOT NULL DEFAULT '*',
+    task_type TEXT,
+    secret TEXT NOT NULL,
+    created_by TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhooks_board ON webhooks(board_id);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    webhook_id BIGINT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts BIGINT NOT NULL DEFAULT 0,
+    response_code BIGINT,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_pending ON webhook_deliveries(status, next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook ON webhook_deliveries(webhook_id, id);
+
+CREATE TABLE IF NOT EXISTS incoming_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    board_id BIGINT NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+    label TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMPTZ
+);
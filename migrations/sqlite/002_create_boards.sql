@@ -0,0 +1,14 @@
+-- 002_create_boards.sql
+CREATE TABLE IF NOT EXISTS boards (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_boards_owner ON boards(owner);
+
+-- Existing tasks keep a NULL board until their owner's first board is created
+ALTER TABLE tasks ADD COLUMN board_id INTEGER REFERENCES boards(id);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_board_bucket_position ON tasks(board_id, bucket, position);
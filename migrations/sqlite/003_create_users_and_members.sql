@@ -0,0 +1,20 @@
+-- 003_create_users_and_members.sql
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS board_members (
+    board_id INTEGER NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    role TEXT NOT NULL CHECK(role IN ('viewer','editor')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (board_id, username)
+);
+
+CREATE INDEX IF NOT EXISTS idx_board_members_username ON board_members(username);
+
+ALTER TABLE tasks ADD COLUMN created_by TEXT;
+ALTER TABLE tasks ADD COLUMN updated_by TEXT;
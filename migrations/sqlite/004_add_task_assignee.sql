@@ -0,0 +1,5 @@
+-- 004_add_task_assignee.sql
+-- A task in the Today bucket sits on its assignee's day plan; everyone else sees it in its quadrant
+ALTER TABLE tasks ADD COLUMN assignee TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_board_assignee ON tasks(board_id, assignee);
@@ -0,0 +1,3 @@
+-- 005_add_task_version.sql
+-- Bumped on every change to a task so clients can detect concurrent edits
+ALTER TABLE tasks ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,14 @@
+-- 006_task_sort_keys.sql
+-- Replace integer positions with fractional sort keys (see src/sort_key.rs) so a
+-- move rewrites only the moved row.
+ALTER TABLE tasks ADD COLUMN sort_key TEXT NOT NULL DEFAULT '';
+
+-- Seed keys from the old positions. The trailing 'V' keeps keys from ending in
+-- the zero digit; the server's rebalance pass shortens them and breaks ties.
+UPDATE tasks SET sort_key = printf('%010d', position) || 'V';
+
+DROP INDEX IF EXISTS idx_tasks_bucket_position;
+DROP INDEX IF EXISTS idx_tasks_board_bucket_position;
+ALTER TABLE tasks DROP COLUMN position;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_board_bucket_sort_key ON tasks(board_id, bucket, sort_key);
@@ -0,0 +1,9 @@
+-- 007_create_task_tags.sql
+-- Free-form tags on tasks, stored normalized (lowercase, no leading '#')
+CREATE TABLE IF NOT EXISTS task_tags (
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (task_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_tags_tag ON task_tags(tag);
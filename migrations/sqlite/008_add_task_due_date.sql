@@ -0,0 +1,3 @@
+-- 008_add_task_due_date.sql
+-- Optional due date (YYYY-MM-DD), set from quick-add's due: marker
+ALTER TABLE tasks ADD COLUMN due_date TEXT;
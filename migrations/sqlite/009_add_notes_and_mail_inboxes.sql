@@ -0,0 +1,10 @@
+-- 009_add_notes_and_mail_inboxes.sql
+-- Free-text notes on tasks (e.g. the body of an emailed task)
+ALTER TABLE tasks ADD COLUMN notes TEXT;
+
+-- Secret per-user address for emailing tasks in: <secret>[+bucket]@<mail domain>
+CREATE TABLE IF NOT EXISTS mail_inboxes (
+    username TEXT PRIMARY KEY,
+    secret TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
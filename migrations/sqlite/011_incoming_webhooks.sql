@@ -0,0 +1,15 @@
+-- 011_incoming_webhooks.sql
+-- Id of the alert/issue in another tool; repeated deliveries update the same task
+ALTER TABLE tasks ADD COLUMN external_id TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tasks_board_external_id ON tasks(board_id, external_id) WHERE external_id IS NOT NULL;
+
+-- Bearer tokens for POST /hooks/tasks; only the SHA-256 of each token is kept
+CREATE TABLE IF NOT EXISTS incoming_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    board_id INTEGER NOT NULL REFERENCES boards(id) ON DELETE CASCADE,
+    label TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    last_used_at TEXT
+);
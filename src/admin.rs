@@ -0,0 +1,347 @@
+// Maintenance subcommands for the server binary, so operators don't need the
+// sqlite3 shell: `eisenpower-rs create-user bob`, `eisenpower-rs backup
+// /backups/tasks.db` and so on. Without a subcommand the server starts.
+//
+// Everything except `restore` works on the live database and is safe to run
+// next to a running server. `backup` and `restore` are SQLite only; use
+// pg_dump and pg_restore for Postgres.
+
+use std::{
+    io::{BufRead, IsTerminal},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    admin_username, backups, config, parse_role, password,
+    storage::{self, sqlite::SqliteStorage, Db},
+    user_exists,
+};
+
+// Bumped when the export layout changes incompatibly
+const EXPORT_FORMAT: i64 = 1;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the web server (the default)
+    Serve,
+    /// Create or upgrade the database schema, then exit
+    Migrate,
+    /// Add a user account
+    CreateUser {
+        username: String,
+        /// Prompted for (or read from stdin) when left out
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Set a new password for a user
+    ResetPassword {
+        username: String,
+        /// Prompted for (or read from stdin) when left out
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Copy the database to a new file, or take a snapshot in backup.dir; safe while the server is running
+    Backup { to: Option<PathBuf> },
+    /// Replace the database with a backup; stop the server first
+    Restore {
+        from: Option<PathBuf>,
+        /// The newest snapshot in backup.dir
+        #[arg(long, conflicts_with = "from")]
+        latest: bool,
+    },
+    /// Write boards, their members and tasks as JSON (users are not included)
+    Export {
+        /// File to write instead of stdout
+        #[arg(long, short)]
+        out: Option<PathBuf>,
+        /// Only this board
+        #[arg(long)]
+        board: Option<i64>,
+    },
+    /// Add the boards from an export as new boards
+    Import {
+        file: PathBuf,
+        /// Give every imported board to this user instead of its original owner
+        #[arg(long)]
+        owner: Option<String>,
+    },
+    /// Reclaim unused space in the database file
+    Vacuum,
+}
+
+pub async fn run(db: &Db, command: Command) -> anyhow::Result<()> {
+    let name = &config::get().database;
+    match command {
+        Command::Serve | Command::Restore { .. } => unreachable!("handled in main"),
+        Command::Migrate => {
+            let (version, description) = db.schema_version().await?;
+            println!("{name} is at migration {version} ({description})");
+        }
+        Command::CreateUser { username, password } => {
+            let username = username.trim();
+            if username.is_empty() || username.contains(':') {
+                bail!("usernames can't be empty or contain ':'");
+            }
+            if user_exists(&**db, username).await? {
+                bail!("user {username} already exists");
+            }
+            let password = new_password(password)?;
+            db.create_user(username, &password::hash(&password)).await?;
+            println!("created user {username}");
+        }
+        Command::ResetPassword { username, password } => {
+            if username == admin_username() {
+                bail!("{username}'s password is set with EISENHOWER_PASSWORD");
+            }
+            if !user_exists(&**db, &username).await? {
+                bail!("no user {username}");
+            }
+            let password = new_password(password)?;
+            db.set_password(&username, &password::hash(&password)).await?;
+            println!("password changed for {username}");
+        }
+        Command::Backup { to: Some(to) } => {
+            db.backup(&to).await?;
+            println!("backed up {name} to {}", to.display());
+        }
+        Command::Backup { to: None } => {
+            let Some(settings) = &config::get().backup else {
+                bail!("give a file to write, or set backup.dir for snapshots");
+            };
+            let snapshot = backups::take(&**db, settings).await?;
+            println!("backed up {name} to {}", snapshot.path.display());
+        }
+        Command::Export { out, board } => {
+            let export = export(db, board).await?;
+            let json = serde_json::to_string_pretty(&export)?;
+            match out {
+                Some(path) => {
+                    std::fs::write(&path, json).with_context(|| format!("could not write {}", path.display()))?;
+                    eprintln!("exported {} boards to {}", export.boards.len(), path.display());
+                }
+                None => println!("{json}"),
+            }
+        }
+        Command::Import { file, owner } => {
+            let raw = std::fs::read_to_string(&file).with_context(|| format!("could not read {}", file.display()))?;
+            let export: Export = serde_json::from_str(&raw).with_context(|| format!("{} is not an export", file.display()))?;
+            if export.format != EXPORT_FORMAT {
+                bail!("unsupported export format {}", export.format);
+            }
+            if let Some(owner) = &owner {
+                if !user_exists(&**db, owner).await? {
+                    bail!("no user {owner}");
+                }
+            }
+            let (boards, tasks) = import(db, export, owner.as_deref()).await?;
+            println!("imported {boards} boards with {tasks} tasks");
+        }
+        Command::Vacuum => {
+            let (before, after) = db.vacuum().await?;
+            println!("{name}: {} KiB -> {} KiB", before / 1024, after / 1024);
+        }
+    }
+    Ok(())
+}
+
+// From --password, a confirmed prompt on a terminal, or the first line of piped stdin
+fn new_password(given: Option<String>) -> anyhow::Result<String> {
+    let password = match given {
+        Some(p) => p,
+        None if std::io::stdin().is_terminal() => {
+            let first = rpassword::prompt_password("New password: ")?;
+            if rpassword::prompt_password("Again: ")? != first {
+                bail!("passwords didn't match");
+            }
+            first
+        }
+        None => {
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    if password.is_empty() {
+        bail!("the password can't be empty");
+    }
+    Ok(password)
+}
+
+/// Swap the database file for the backup at `from`, keeping a copy of what was there.
+/// Older backups are migrated forward; newer ones are refused.
+pub async fn restore(database: &config::Database, from: Option<PathBuf>, latest: bool) -> anyhow::Result<()> {
+    let Some(db_path) = database.sqlite_path() else {
+        bail!("restore only works on SQLite files; use pg_restore for Postgres");
+    };
+    let snapshots = config::get().backup.as_ref().map(|b| backups::list(&b.dir)).unwrap_or_default();
+    let from = match (from, snapshots.first()) {
+        (Some(from), _) => from,
+        (None, Some(newest)) if latest => newest.path.clone(),
+        (None, _) if snapshots.is_empty() => bail!("no snapshots found; give the backup file to restore"),
+        (None, _) => {
+            let names: Vec<String> = snapshots.iter().map(|s| s.path.display().to_string()).collect();
+            bail!("give the backup file to restore, or --latest. Snapshots:\n  {}", names.join("\n  "))
+        }
+    };
+    let from = from.as_path();
+    let version = storage::sqlite::file_schema_version(from).await?;
+    let known = storage::sqlite::MIGRATOR.migrations.iter().map(|m| m.version).max().unwrap_or(0);
+    if version > known {
+        bail!("{} comes from a newer version (migration {version}); upgrade first", from.display());
+    }
+
+    let name = db_path.display();
+    let pool_size = config::get().pool_size;
+    if db_path.exists() {
+        let current = SqliteStorage::open(db_path, pool_size).await?;
+        let keep = PathBuf::from(format!("{name}.before-restore-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        storage::Storage::backup(&current, &keep).await?;
+        current.close().await;
+        println!("saved the current database as {}", keep.display());
+    }
+    // The old write-ahead log belongs to the old file
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{name}{suffix}"));
+    }
+    std::fs::copy(from, db_path).with_context(|| format!("could not copy {} to {name}", from.display()))?;
+    SqliteStorage::open(db_path, pool_size).await?.close().await;
+    println!("restored {name} from {}", from.display());
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct Export {
+    format: i64,
+    exported_at: String,
+    boards: Vec<ExportBoard>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportBoard {
+    name: String,
+    owner: String,
+    members: Vec<ExportMember>,
+    tasks: Vec<ExportTask>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportMember {
+    username: String,
+    role: String,
+}
+
+// Dates and times as SQLite stores them, whichever backend wrote the export
+#[derive(Serialize, Deserialize, sqlx::FromRow)]
+pub struct ExportTask {
+    #[serde(skip)]
+    pub id: i64,
+    pub title: String,
+    pub task_type: String,
+    pub bucket: String,
+    pub completed: bool,
+    pub sort_key: String,
+    pub created_by: Option<String>,
+    pub updated_by: Option<String>,
+    pub assignee: Option<String>,
+    pub due_date: Option<String>,
+    pub notes: Option<String>,
+    pub external_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+async fn export(db: &Db, only: Option<i64>) -> anyhow::Result<Export> {
+    let boards = db.all_boards(only).await?;
+    if let (Some(id), true) = (only, boards.is_empty()) {
+        bail!("no board {id}");
+    }
+    let mut out = Vec::new();
+    for (id, name, owner) in boards {
+        let members = db
+            .members(id)
+            .await?
+            .into_iter()
+            .map(|(username, role)| ExportMember { username, role: role.as_str().to_string() })
+            .collect();
+        let tasks = db.export_tasks(id).await?;
+        out.push(ExportBoard { name, owner, members, tasks });
+    }
+    Ok(Export { format: EXPORT_FORMAT, exported_at: chrono::Utc::now().to_rfc3339(), boards: out })
+}
+
+// All or nothing: one transaction for the whole file
+async fn import(db: &Db, export: Export, owner: Option<&str>) -> anyhow::Result<(usize, usize)> {
+    let mut tx = db.begin().await?;
+    let mut task_count = 0;
+    for board in &export.boards {
+        let owner = owner.unwrap_or(&board.owner);
+        let board_id = tx.create_board(&board.name, owner).await?;
+        for m in board.members.iter().filter(|m| m.username != owner) {
+            let Some(role) = parse_role(&m.role) else {
+                bail!("member {} of board {} has unknown role {}", m.username, board.name, m.role);
+            };
+            tx.add_member(board_id, &m.username, role)
+                .await
+                .with_context(|| format!("member {} of board {}", m.username, board.name))?;
+        }
+        for t in &board.tasks {
+            let task_id = tx
+                .import_task(board_id, t)
+                .await
+                .with_context(|| format!("task \"{}\" on board {}", t.title, board.name))?;
+            for tag in &t.tags {
+                tx.add_tag(task_id, tag).await?;
+            }
+            task_count += 1;
+        }
+    }
+    tx.commit().await?;
+    Ok((export.boards.len(), task_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{create_task, user_boards, Bucket, Role, TaskDraft};
+
+    async fn boards(db: &Db) -> serde_json::Value {
+        serde_json::to_value(export(db, None).await.unwrap().boards).unwrap()
+    }
+
+    // From the backend under test (see storage::scratch) to SQLite and back
+    #[tokio::test]
+    async fn exports_come_back_the_same_from_either_backend() {
+        config::set_for_tests();
+        let db = storage::scratch().await;
+        let board = user_boards(&*db, "alice").await.unwrap()[0].id;
+        db.set_member(board, "bob", Role::Editor).await.unwrap();
+        let mut draft = TaskDraft::new("file taxes".to_string(), Bucket::NotUrgentImportant);
+        draft.tags = vec!["money".to_string(), "home".to_string()];
+        draft.due_date = NaiveDate::from_ymd_opt(2026, 4, 15);
+        draft.notes = Some("receipts in the drawer".to_string());
+        create_task(&*db, board, "alice", draft).await.unwrap();
+        create_task(&*db, board, "bob", TaskDraft::new("call mum".to_string(), Bucket::Today)).await.unwrap();
+        let original = boards(&db).await;
+
+        let sqlite: Db = Arc::new(SqliteStorage::memory().await);
+        let copy = |value: &serde_json::Value| serde_json::from_value(serde_json::json!({ "format": EXPORT_FORMAT, "exported_at": "", "boards": value })).unwrap();
+        assert_eq!(import(&sqlite, copy(&original), None).await.unwrap(), (1, 2));
+        assert_eq!(boards(&sqlite).await, original);
+
+        let back = storage::scratch().await;
+        import(&back, copy(&boards(&sqlite).await), None).await.unwrap();
+        assert_eq!(boards(&back).await, original);
+        assert_eq!(original[0]["tasks"].as_array().unwrap().len(), 2);
+    }
+}
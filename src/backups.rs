@@ -0,0 +1,253 @@
+// Scheduled snapshots of the database.
+//
+// When `backup.dir` is set, a background job writes `<db name>-YYYYMMDD-HHMMSS.db`
+// (UTC) there every `backup.interval` with VACUUM INTO, which is safe while the
+// server is running. After each snapshot the directory is thinned out: the
+// newest snapshot of each of the last `keep_daily` days and of each of the last
+// `keep_weekly` ISO weeks survive, everything else is deleted. Restore one with
+// `eisenpower-rs restore --latest` (or a path) with the server stopped.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::{
+    config,
+    storage::{Db, Storage},
+};
+
+const STAMP: &str = "%Y%m%d-%H%M%S";
+const RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Clone, Serialize)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub taken_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+// What the job last did, for the status endpoint
+struct Status {
+    last_error: Option<(DateTime<Utc>, String)>,
+    next_at: Option<DateTime<Utc>>,
+}
+
+static STATUS: Mutex<Status> = Mutex::new(Status { last_error: None, next_at: None });
+
+// Snapshots are named after the database file: tasks.db -> tasks-20250101-120000.db
+fn prefix() -> String {
+    let stem = config::get().database.sqlite_path().and_then(Path::file_stem).map(|s| s.to_string_lossy().into_owned());
+    format!("{}-", stem.unwrap_or_else(|| "tasks".to_string()))
+}
+
+/// Snapshots in `dir`, newest first.
+pub fn list(dir: &Path) -> Vec<Snapshot> {
+    scan(dir, &prefix())
+}
+
+fn scan(dir: &Path, prefix: &str) -> Vec<Snapshot> {
+    let mut snapshots: Vec<Snapshot> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stamp = name.strip_prefix(prefix)?.strip_suffix(".db")?;
+            let taken_at = NaiveDateTime::parse_from_str(stamp, STAMP).ok()?.and_utc();
+            let size_bytes = entry.metadata().ok()?.len();
+            Some(Snapshot { path: entry.path(), taken_at, size_bytes })
+        })
+        .collect();
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.taken_at));
+    snapshots
+}
+
+/// Write a new snapshot into `dir`, then apply the rotation policy.
+pub async fn take(db: &dyn Storage, settings: &config::Backup) -> anyhow::Result<Snapshot> {
+    std::fs::create_dir_all(&settings.dir).with_context(|| format!("could not create {}", settings.dir.display()))?;
+    let taken_at = Utc::now();
+    let path = settings.dir.join(format!("{}{}.db", prefix(), taken_at.format(STAMP)));
+    // Written under a temporary name so a crash never leaves a half snapshot that looks complete
+    let partial = path.with_extension("db.partial");
+    let _ = std::fs::remove_file(&partial);
+    db.backup(&partial).await?;
+    std::fs::rename(&partial, &path).with_context(|| format!("could not rename {}", partial.display()))?;
+    let size_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    for old in rotate(list(&settings.dir), settings.keep_daily, settings.keep_weekly) {
+        match std::fs::remove_file(&old) {
+            Ok(()) => info!(path = %old.display(), "removed old backup"),
+            Err(e) => warn!(path = %old.display(), error = %e, "could not remove old backup"),
+        }
+    }
+    Ok(Snapshot { path, taken_at, size_bytes })
+}
+
+// Snapshots (newest first, as from `list`) that fall outside the policy. The
+// newest one is always kept.
+fn rotate(snapshots: Vec<Snapshot>, keep_daily: usize, keep_weekly: usize) -> Vec<PathBuf> {
+    let mut keep = HashSet::new();
+    let mut days = Vec::new();
+    let mut weeks = Vec::new();
+    for (i, s) in snapshots.iter().enumerate() {
+        let day = s.taken_at.date_naive();
+        let week = (day.iso_week().year(), day.iso_week().week());
+        if !days.contains(&day) {
+            days.push(day);
+            if days.len() <= keep_daily {
+                keep.insert(i);
+            }
+        }
+        if !weeks.contains(&week) {
+            weeks.push(week);
+            if weeks.len() <= keep_weekly {
+                keep.insert(i);
+            }
+        }
+    }
+    keep.insert(0);
+    snapshots
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !keep.contains(i))
+        .map(|(_, s)| s.path)
+        .collect()
+}
+
+/// Take snapshots on schedule until the process exits.
+pub async fn run(db: Db, settings: &'static config::Backup) {
+    let interval = chrono::Duration::from_std(settings.interval).unwrap_or(chrono::Duration::days(1));
+    loop {
+        // Due one interval after the newest snapshot, so restarts don't reset the clock
+        let due = list(&settings.dir).first().map(|s| s.taken_at + interval).unwrap_or_else(Utc::now);
+        let now = Utc::now();
+        if due > now {
+            set_status(|s| s.next_at = Some(due));
+            tokio::time::sleep((due - now).to_std().unwrap_or(RETRY_AFTER)).await;
+            continue;
+        }
+        match take(&*db, settings).await {
+            Ok(snapshot) => {
+                info!(path = %snapshot.path.display(), bytes = snapshot.size_bytes, "backup written");
+                set_status(|s| s.last_error = None);
+            }
+            Err(e) => {
+                warn!(error = %format!("{e:#}"), "backup failed");
+                let retry = Utc::now() + chrono::Duration::from_std(RETRY_AFTER.min(settings.interval)).unwrap_or_default();
+                set_status(|s| {
+                    s.last_error = Some((Utc::now(), format!("{e:#}")));
+                    s.next_at = Some(retry);
+                });
+                tokio::time::sleep(RETRY_AFTER.min(settings.interval)).await;
+            }
+        }
+    }
+}
+
+fn set_status(f: impl FnOnce(&mut Status)) {
+    if let Ok(mut status) = STATUS.lock() {
+        f(&mut status);
+    }
+}
+
+/// JSON for the status endpoint, and whether the newest snapshot is recent
+/// enough (no more than two intervals old).
+pub fn status() -> (bool, serde_json::Value) {
+    let Some(settings) = &config::get().backup else {
+        return (true, json!({ "enabled": false }));
+    };
+    let snapshots = list(&settings.dir);
+    let latest = snapshots.first();
+    let max_age = chrono::Duration::from_std(settings.interval * 2).unwrap_or(chrono::Duration::days(2));
+    let healthy = latest.is_some_and(|s| Utc::now() - s.taken_at <= max_age);
+    let (last_error, next_at) = match STATUS.lock() {
+        Ok(s) => (s.last_error.clone(), s.next_at),
+        Err(_) => (None, None),
+    };
+    let body = json!({
+        "enabled": true,
+        "healthy": healthy,
+        "dir": settings.dir,
+        "interval_secs": settings.interval.as_secs(),
+        "keep_daily": settings.keep_daily,
+        "keep_weekly": settings.keep_weekly,
+        "snapshots": snapshots.len(),
+        "last_backup": latest,
+        "last_error": last_error.map(|(at, message)| json!({ "at": at, "message": message })),
+        "next_backup_at": next_at,
+    });
+    (healthy, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const PREFIX: &str = "tasks-";
+
+    // One snapshot every six hours for `days` days, the last at 18:00 on `last_day`
+    fn fill(dir: &Path, last_day: NaiveDate, days: u64) {
+        let end = last_day.and_hms_opt(18, 0, 0).unwrap();
+        for i in 0..days * 4 {
+            let at = end - chrono::Duration::hours(6 * i as i64);
+            std::fs::write(dir.join(format!("{PREFIX}{}.db", at.format(STAMP))), b"").unwrap();
+        }
+    }
+
+    fn apply(dir: &Path, keep_daily: usize, keep_weekly: usize) -> Vec<String> {
+        for old in rotate(scan(dir, PREFIX), keep_daily, keep_weekly) {
+            std::fs::remove_file(old).unwrap();
+        }
+        scan(dir, PREFIX).iter().map(|s| s.taken_at.format(STAMP).to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_the_newest_of_each_recent_day_and_week() {
+        let dir = tempfile::tempdir().unwrap();
+        // Sunday 2025-03-09 ends ISO week 10
+        fill(dir.path(), NaiveDate::from_ymd_opt(2025, 3, 9).unwrap(), 40);
+        std::fs::write(dir.path().join("notes.txt"), b"").unwrap();
+        std::fs::write(dir.path().join("tasks-garbage.db"), b"").unwrap();
+        let kept = apply(dir.path(), 7, 4);
+        assert_eq!(
+            kept,
+            [
+                "20250309-180000", "20250308-180000", "20250307-180000", "20250306-180000",
+                "20250305-180000", "20250304-180000", "20250303-180000", // last 7 days
+                "20250302-180000", "20250223-180000", "20250216-180000", // weeks 9, 8 and 7
+            ]
+        );
+        // Files that aren't snapshots are left alone
+        assert!(dir.path().join("notes.txt").exists() && dir.path().join("tasks-garbage.db").exists());
+        // Running it again changes nothing
+        assert_eq!(apply(dir.path(), 7, 4), kept);
+    }
+
+    #[test]
+    fn fewer_snapshots_than_the_policy_allows_are_all_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        fill(dir.path(), NaiveDate::from_ymd_opt(2025, 3, 9).unwrap(), 1);
+        assert_eq!(apply(dir.path(), 7, 4).len(), 1); // only the newest of the day
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("tasks-20250309-180000.db"), b"").unwrap();
+        std::fs::write(dir.path().join("tasks-20250301-180000.db"), b"").unwrap();
+        assert_eq!(apply(dir.path(), 7, 4).len(), 2);
+    }
+
+    #[test]
+    fn weekly_only_and_the_newest_always_survives() {
+        let dir = tempfile::tempdir().unwrap();
+        fill(dir.path(), NaiveDate::from_ymd_opt(2025, 3, 9).unwrap(), 14);
+        assert_eq!(apply(dir.path(), 0, 2), ["20250309-180000", "20250302-180000"]);
+        assert_eq!(apply(dir.path(), 0, 0), ["20250309-180000"]);
+    }
+}
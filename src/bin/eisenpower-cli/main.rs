@@ -0,0 +1,272 @@
+// Command-line client for an eisenpower server.
+//
+// Reads the server URL and credentials from a TOML file,
+// $EISENPOWER_CLI_CONFIG or ~/.config/eisenpower/cli.toml by default:
+//
+//   url = "http://127.0.0.1:8080"
+//   username = "admin"
+//   password = "password"
+//   board = 1            # optional; defaults to your first board
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+mod tui;
+
+const COLUMNS: [(&str, &str); 5] = [
+    ("UrgentImportant", "Urgent & Important"),
+    ("UrgentNotImportant", "Urgent & Not Important"),
+    ("Today", "Today's Tasks"),
+    ("NotUrgentImportant", "Not Urgent & Important"),
+    ("NotUrgentNotImportant", "Not Urgent & Not Important"),
+];
+
+#[derive(Parser)]
+#[command(name = "eisenpower-cli", about = "Work with your Eisenhower matrix from the terminal")]
+struct Cli {
+    /// Config file (default: $EISENPOWER_CLI_CONFIG or ~/.config/eisenpower/cli.toml)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Board id (default: the config file's `board`, else your first board)
+    #[arg(long, global = true)]
+    board: Option<i64>,
+    /// Print JSON instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List open tasks grouped by column
+    List,
+    /// Add a task. Without --quadrant, quick-add markers (!u !i @today #tag due:fri) in the title decide
+    Add {
+        /// ui, uni, nui, nun or today
+        #[arg(long, short)]
+        quadrant: Option<String>,
+        title: Vec<String>,
+    },
+    /// Move tasks to your Today list
+    Today { ids: Vec<i64> },
+    /// Mark tasks as completed
+    Complete { ids: Vec<i64> },
+    /// Find open tasks by title, tag or notes
+    Search { query: Vec<String> },
+    /// List your boards
+    Boards,
+    /// Full-screen board view with keyboard drag and drop
+    Tui,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    url: String,
+    username: String,
+    password: String,
+    board: Option<i64>,
+}
+
+struct Client {
+    http: reqwest::Client,
+    config: Config,
+}
+
+impl Client {
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.url.trim_end_matches('/'), path)
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> anyhow::Result<Value> {
+        let resp = req
+            .basic_auth(&self.config.username, Some(&self.config.password))
+            .send()
+            .await
+            .with_context(|| format!("could not reach {}", self.config.url))?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        if status == reqwest::StatusCode::PRECONDITION_FAILED {
+            bail!("the task was changed elsewhere; reload and try again");
+        }
+        if !status.is_success() {
+            // API errors come as {"error": "..."}
+            let message = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|v| v["error"].as_str().map(str::to_string))
+                .unwrap_or_else(|| text.trim().to_string());
+            bail!("server answered {}: {}", status, message);
+        }
+        Ok(serde_json::from_str(&text).unwrap_or(Value::Null))
+    }
+
+    async fn get(&self, path: &str, query: &[(&str, String)]) -> anyhow::Result<Value> {
+        self.send(self.http.get(self.url(path)).query(query)).await
+    }
+
+    // The server wants this on every change; it is tied to the user and the server's run
+    async fn csrf_token(&self) -> anyhow::Result<String> {
+        let data = self.get("/csrf", &[]).await?;
+        data["token"].as_str().map(str::to_string).context("server sent no CSRF token")
+    }
+
+    async fn post(&self, path: &str, body: Value) -> anyhow::Result<Value> {
+        let token = self.csrf_token().await?;
+        self.send(self.http.post(self.url(path)).header("X-CSRF-Token", token).json(&body)).await
+    }
+
+    async fn patch(&self, path: &str, body: Value) -> anyhow::Result<Value> {
+        let token = self.csrf_token().await?;
+        self.send(self.http.patch(self.url(path)).header("X-CSRF-Token", token).json(&body)).await
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run(Cli::parse()).await {
+        eprintln!("error: {e:#}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    let config = load_config(cli.config.as_ref())?;
+    let board = cli.board.or(config.board);
+    let client = Client { http: reqwest::Client::new(), config };
+    let mut board_query = Vec::new();
+    if let Some(b) = board {
+        board_query.push(("board", b.to_string()));
+    }
+
+    match cli.command {
+        Command::List | Command::Search { .. } => {
+            let mut query = board_query.clone();
+            if let Command::Search { query: words } = &cli.command {
+                query.push(("q", words.join(" ")));
+            }
+            let data = client.get("/api/tasks", &query).await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&data)?);
+            } else {
+                print_matrix(&data);
+            }
+        }
+        Command::Add { quadrant, title } => {
+            let mut body = json!({ "title": title.join(" "), "board": board });
+            if let Some(q) = quadrant {
+                body["bucket"] = json!(q);
+            }
+            let task = client.post("/api/tasks", body).await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&task)?);
+            } else {
+                println!("Added #{} to {}", task["id"], column_label(task["bucket"].as_str().unwrap_or_default()));
+            }
+        }
+        Command::Today { ids } => bulk(&client, cli.json, json!({ "action": "move", "bucket": "Today", "ids": ids }), "Moved to Today").await?,
+        Command::Complete { ids } => bulk(&client, cli.json, json!({ "action": "complete", "ids": ids }), "Completed").await?,
+        Command::Tui => tui::run(&client, board).await?,
+        Command::Boards => {
+            let boards = client.get("/api/boards", &[]).await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&boards)?);
+            } else {
+                let rows: Vec<Vec<String>> = boards
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|b| vec![b["id"].to_string(), text(&b["name"]), text(&b["owner"]), text(&b["role"]).to_lowercase()])
+                    .collect();
+                print_table(&["ID", "NAME", "OWNER", "ROLE"], &rows);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn bulk(client: &Client, as_json: bool, body: Value, done: &str) -> anyhow::Result<()> {
+    if body["ids"].as_array().is_none_or(|ids| ids.is_empty()) {
+        bail!("give at least one task id");
+    }
+    let data = client.post("/tasks/bulk", body).await?;
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&data)?);
+        return Ok(());
+    }
+    let mut failed = false;
+    for r in data["results"].as_array().into_iter().flatten() {
+        if r["ok"].as_bool() == Some(true) {
+            println!("{} #{}", done, r["id"]);
+        } else {
+            failed = true;
+            eprintln!("Skipped #{}: {}", r["id"], text(&r["error"]));
+        }
+    }
+    if failed {
+        bail!("some tasks were skipped");
+    }
+    Ok(())
+}
+
+fn load_config(path: Option<&PathBuf>) -> anyhow::Result<Config> {
+    let path = match path {
+        Some(p) => p.clone(),
+        None => match std::env::var_os("EISENPOWER_CLI_CONFIG") {
+            Some(p) => PathBuf::from(p),
+            None => {
+                let home = std::env::var_os("HOME").context("HOME is not set; pass --config")?;
+                PathBuf::from(home).join(".config/eisenpower/cli.toml")
+            }
+        },
+    };
+    let raw = std::fs::read_to_string(&path).with_context(|| format!("could not read config {}", path.display()))?;
+    toml::from_str(&raw).with_context(|| format!("invalid config {}", path.display()))
+}
+
+fn column_label(bucket: &str) -> &str {
+    COLUMNS.iter().find(|(b, _)| *b == bucket).map(|(_, label)| *label).unwrap_or(bucket)
+}
+
+fn text(v: &Value) -> String {
+    v.as_str().map(str::to_string).unwrap_or_default()
+}
+
+fn print_matrix(data: &Value) {
+    println!("Board: {}", text(&data["board"]["name"]));
+    for (bucket, label) in COLUMNS {
+        let tasks = data["columns"][bucket].as_array().cloned().unwrap_or_default();
+        println!("\n{} ({})", label, tasks.len());
+        if tasks.is_empty() {
+            continue;
+        }
+        let rows: Vec<Vec<String>> = tasks
+            .iter()
+            .map(|t| {
+                let tags = t["tags"].as_array().into_iter().flatten().map(|tag| format!("#{}", text(tag))).collect::<Vec<_>>().join(" ");
+                vec![t["id"].to_string(), text(&t["title"]), tags, text(&t["due_date"]), text(&t["assignee"])]
+            })
+            .collect();
+        print_table(&["ID", "TITLE", "TAGS", "DUE", "ASSIGNEE"], &rows);
+    }
+}
+
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(c, w)| format!("{:<w$}", c, w = *w)).collect();
+        println!("  {}", padded.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
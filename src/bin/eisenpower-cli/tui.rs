@@ -0,0 +1,510 @@
+// Full-screen board view for terminals (and SSH sessions) without a browser.
+//
+// Laid out like the web page: the four quadrants in a 2x2 grid with Today's
+// Tasks down the right. Space picks a card up, the arrow keys carry it within
+// and between columns, and Space or Enter drops it there. The board reloads
+// every few seconds so other people's changes show up.
+
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{Client, COLUMNS};
+
+const RELOAD_EVERY: Duration = Duration::from_secs(3);
+
+// Column colours from style.css (in COLUMNS order), blue lightened to read on dark terminals
+const COLOURS: [Color; 5] = [
+    Color::Rgb(0xA7, 0x44, 0x41),
+    Color::Rgb(0xA7, 0x88, 0x41),
+    Color::Rgb(0x90, 0x90, 0x90),
+    Color::Rgb(0x5A, 0x6C, 0xB4),
+    Color::Rgb(0x33, 0x83, 0x39),
+];
+
+// Where the arrow keys lead from each column on the grid:
+// UI  UNI  TODAY
+// NUI NUN  TODAY
+const LEFT: [usize; 5] = [0, 0, 1, 3, 3];
+const RIGHT: [usize; 5] = [1, 2, 2, 4, 2];
+const ABOVE: [Option<usize>; 5] = [None, None, None, Some(0), Some(1)];
+const BELOW: [Option<usize>; 5] = [Some(3), Some(4), None, None, None];
+
+// Digit shortcuts, as on the web page
+const QUADRANT_KEYS: [(char, usize); 5] = [('1', 0), ('2', 1), ('3', 3), ('4', 4), ('t', 2)];
+
+const HELP: [(&str, &str); 14] = [
+    ("arrows / hjkl", "move between cards and columns"),
+    ("space", "pick up a card; arrows carry it, space or enter drops it"),
+    ("esc", "put a carried card back"),
+    ("1 2 3 4", "send to Urgent & Important ... Not Urgent & Not Important"),
+    ("t", "send to Today"),
+    ("e / enter", "edit the title"),
+    ("a", "add a task to this column"),
+    ("n", "quick-add: !u !i @today #tag due:fri decide"),
+    ("x", "mark as completed"),
+    ("D / delete", "delete (asks first)"),
+    ("/", "search titles and tags"),
+    ("r", "reload now"),
+    ("?", "show or hide this help"),
+    ("q", "quit"),
+];
+
+#[derive(Deserialize, Clone)]
+struct Task {
+    id: i64,
+    title: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    due_date: Option<String>,
+    assignee: Option<String>,
+    version: i64,
+}
+
+enum Mode {
+    Browse,
+    // Carrying the focused card; `origin` is where it was picked up
+    Carry { origin: (usize, usize) },
+    Edit { id: i64, text: String },
+    Add { quick: bool, text: String },
+    Search,
+    ConfirmDelete { id: i64, title: String },
+    Help,
+}
+
+struct App {
+    board: Option<i64>,
+    board_name: String,
+    columns: [Vec<Task>; 5],
+    focus: usize,
+    cursor: [usize; 5],
+    filter: String,
+    mode: Mode,
+    status: String,
+    loaded_at: Instant,
+    quit: bool,
+}
+
+pub async fn run(client: &Client, board: Option<i64>) -> anyhow::Result<()> {
+    let mut app = App {
+        board,
+        board_name: String::new(),
+        columns: Default::default(),
+        focus: 0,
+        cursor: [0; 5],
+        filter: String::new(),
+        mode: Mode::Browse,
+        status: String::new(),
+        loaded_at: Instant::now(),
+        quit: false,
+    };
+    // Fail before taking over the screen if the server can't be reached
+    app.reload(client).await?;
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(&mut terminal, client).await;
+    ratatui::restore();
+    result
+}
+
+impl App {
+    async fn event_loop(&mut self, terminal: &mut DefaultTerminal, client: &Client) -> anyhow::Result<()> {
+        while !self.quit {
+            terminal.draw(|f| draw(f, self))?;
+            if event::poll(Duration::from_millis(250))? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.on_key(client, key).await;
+                    }
+                }
+            }
+            // Leave the board alone while a card is in the air
+            if !matches!(self.mode, Mode::Carry { .. }) && self.loaded_at.elapsed() >= RELOAD_EVERY {
+                if let Err(e) = self.reload(client).await {
+                    self.status = format!("{e:#}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn reload(&mut self, client: &Client) -> anyhow::Result<()> {
+        self.loaded_at = Instant::now();
+        let mut query = Vec::new();
+        if let Some(b) = self.board {
+            query.push(("board", b.to_string()));
+        }
+        let data = client.get("/api/tasks", &query).await?;
+        let selected = self.selected().map(|t| t.id);
+        self.board = data["board"]["id"].as_i64();
+        self.board_name = data["board"]["name"].as_str().unwrap_or_default().to_string();
+        for (i, (bucket, _)) in COLUMNS.iter().enumerate() {
+            self.columns[i] = serde_json::from_value(data["columns"][bucket].clone()).unwrap_or_default();
+        }
+        // Keep the cursor on the same card if it's still in this column
+        if let Some(i) = selected.and_then(|id| self.visible(self.focus).iter().position(|t| t.id == id)) {
+            self.cursor[self.focus] = i;
+        }
+        self.clamp();
+        Ok(())
+    }
+
+    fn visible(&self, col: usize) -> Vec<&Task> {
+        let needle = self.filter.to_lowercase();
+        self.columns[col]
+            .iter()
+            .filter(|t| needle.is_empty() || t.title.to_lowercase().contains(&needle) || t.tags.iter().any(|tag| tag.contains(&needle)))
+            .collect()
+    }
+
+    fn selected(&self) -> Option<&Task> {
+        self.visible(self.focus).get(self.cursor[self.focus]).copied()
+    }
+
+    // Focus the card with `id` if it's on screen; otherwise keep the cursors in range
+    fn select(&mut self, id: i64) {
+        for col in 0..COLUMNS.len() {
+            if let Some(i) = self.visible(col).iter().position(|t| t.id == id) {
+                self.focus = col;
+                self.cursor[col] = i;
+            }
+        }
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        for col in 0..COLUMNS.len() {
+            let len = self.visible(col).len();
+            self.cursor[col] = self.cursor[col].min(len.saturating_sub(1));
+        }
+    }
+
+    async fn on_key(&mut self, client: &Client, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        self.status.clear();
+        let result = match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.browse_key(client, key).await,
+            Mode::Carry { origin } => self.carry_key(client, key, origin).await,
+            Mode::Edit { id, mut text } => match edit_text(&mut text, key) {
+                Some(true) => self.save_title(client, id, text).await,
+                Some(false) => Ok(()),
+                None => {
+                    self.mode = Mode::Edit { id, text };
+                    Ok(())
+                }
+            },
+            Mode::Add { quick, mut text } => match edit_text(&mut text, key) {
+                Some(true) => self.add(client, quick, text).await,
+                Some(false) => Ok(()),
+                None => {
+                    self.mode = Mode::Add { quick, text };
+                    Ok(())
+                }
+            },
+            Mode::Search => {
+                match edit_text(&mut self.filter, key) {
+                    Some(true) => {}
+                    Some(false) => self.filter.clear(),
+                    None => self.mode = Mode::Search,
+                }
+                self.clamp();
+                Ok(())
+            }
+            Mode::ConfirmDelete { id, .. } if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) => {
+                self.bulk(client, json!({ "action": "delete", "ids": [id] })).await
+            }
+            Mode::ConfirmDelete { .. } => Ok(()),
+            Mode::Help => Ok(()),
+        };
+        if let Err(e) = result {
+            self.status = format!("{e:#}");
+            // Whatever we showed is probably stale now
+            let _ = self.reload(client).await;
+        }
+    }
+
+    async fn browse_key(&mut self, client: &Client, key: KeyEvent) -> anyhow::Result<()> {
+        let col = self.focus;
+        let len = self.visible(col).len();
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.clamp();
+            }
+            KeyCode::Left | KeyCode::Char('h') => self.focus = LEFT[col],
+            KeyCode::Right | KeyCode::Char('l') => self.focus = RIGHT[col],
+            KeyCode::Up | KeyCode::Char('k') => {
+                if self.cursor[col] > 0 {
+                    self.cursor[col] -= 1;
+                } else if let Some(above) = ABOVE[col] {
+                    self.focus = above;
+                    self.cursor[above] = self.visible(above).len().saturating_sub(1);
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self.cursor[col] + 1 < len {
+                    self.cursor[col] += 1;
+                } else if let Some(below) = BELOW[col] {
+                    self.focus = below;
+                    self.cursor[below] = 0;
+                }
+            }
+            KeyCode::Char('?') => self.mode = Mode::Help,
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Char('r') => self.reload(client).await?,
+            KeyCode::Char('a') => self.mode = Mode::Add { quick: false, text: String::new() },
+            KeyCode::Char('n') => self.mode = Mode::Add { quick: true, text: String::new() },
+            _ => {
+                let Some(task) = self.selected().cloned() else {
+                    return Ok(());
+                };
+                match key.code {
+                    KeyCode::Char(' ') if !self.filter.is_empty() => {
+                        self.status = "clear the search (esc) to drag cards".to_string();
+                    }
+                    KeyCode::Char(' ') => self.mode = Mode::Carry { origin: (col, self.cursor[col]) },
+                    KeyCode::Char('e') | KeyCode::Enter => self.mode = Mode::Edit { id: task.id, text: task.title },
+                    KeyCode::Char('x') => self.bulk(client, json!({ "action": "complete", "ids": [task.id] })).await?,
+                    KeyCode::Char('D') | KeyCode::Delete => self.mode = Mode::ConfirmDelete { id: task.id, title: task.title },
+                    KeyCode::Char(c) => {
+                        if let Some(&(_, to)) = QUADRANT_KEYS.iter().find(|(k, _)| *k == c.to_ascii_lowercase()) {
+                            if to != col {
+                                // Lands at the bottom of the column, like the web shortcuts
+                                let prev = self.columns[to].last().map(|t| t.id);
+                                self.send_move(client, &task, to, prev, None).await?;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Arrow keys shift the card around locally; nothing is sent until it's dropped
+    async fn carry_key(&mut self, client: &Client, key: KeyEvent, origin: (usize, usize)) -> anyhow::Result<()> {
+        let col = self.focus;
+        let i = self.cursor[col];
+        match key.code {
+            KeyCode::Char(' ') | KeyCode::Enter => {
+                if (col, i) == origin {
+                    return Ok(());
+                }
+                let task = self.columns[col][i].clone();
+                let prev = i.checked_sub(1).map(|p| self.columns[col][p].id);
+                let next = self.columns[col].get(i + 1).map(|t| t.id);
+                return self.send_move(client, &task, col, prev, next).await;
+            }
+            KeyCode::Esc => {
+                self.reload(client).await?;
+                self.focus = origin.0;
+                self.cursor[origin.0] = origin.1;
+                self.clamp();
+                return Ok(());
+            }
+            KeyCode::Up | KeyCode::Char('k') if i > 0 => {
+                self.columns[col].swap(i, i - 1);
+                self.cursor[col] -= 1;
+            }
+            KeyCode::Down | KeyCode::Char('j') if i + 1 < self.columns[col].len() => {
+                self.columns[col].swap(i, i + 1);
+                self.cursor[col] += 1;
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                if let Some(above) = ABOVE[col] {
+                    let len = self.columns[above].len();
+                    self.carry_to(above, len);
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if let Some(below) = BELOW[col] {
+                    self.carry_to(below, 0);
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => self.carry_to(LEFT[col], i),
+            KeyCode::Right | KeyCode::Char('l') => self.carry_to(RIGHT[col], i),
+            _ => {}
+        }
+        self.mode = Mode::Carry { origin };
+        Ok(())
+    }
+
+    fn carry_to(&mut self, to: usize, at: usize) {
+        let from = self.focus;
+        if to == from {
+            return;
+        }
+        let task = self.columns[from].remove(self.cursor[from]);
+        let at = at.min(self.columns[to].len());
+        self.columns[to].insert(at, task);
+        self.focus = to;
+        self.cursor[to] = at;
+        self.clamp();
+    }
+
+    async fn send_move(&mut self, client: &Client, task: &Task, to: usize, prev: Option<i64>, next: Option<i64>) -> anyhow::Result<()> {
+        let body = json!({ "id": task.id, "bucket": COLUMNS[to].0, "prevId": prev, "nextId": next, "version": task.version });
+        client.post("/move", body).await?;
+        self.reload(client).await?;
+        self.select(task.id);
+        Ok(())
+    }
+
+    async fn save_title(&mut self, client: &Client, id: i64, text: String) -> anyhow::Result<()> {
+        let title = text.trim();
+        let Some(task) = self.columns.iter().flatten().find(|t| t.id == id) else {
+            anyhow::bail!("that task is gone");
+        };
+        if title.is_empty() || title == task.title {
+            return Ok(());
+        }
+        client.patch(&format!("/tasks/{id}"), json!({ "title": title, "version": task.version })).await?;
+        self.reload(client).await
+    }
+
+    async fn add(&mut self, client: &Client, quick: bool, text: String) -> anyhow::Result<()> {
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        let mut body = json!({ "title": text, "board": self.board });
+        if !quick {
+            body["bucket"] = json!(COLUMNS[self.focus].0);
+        }
+        let task = client.post("/api/tasks", body).await?;
+        self.reload(client).await?;
+        if let Some(id) = task["id"].as_i64() {
+            self.select(id);
+        }
+        Ok(())
+    }
+
+    async fn bulk(&mut self, client: &Client, body: Value) -> anyhow::Result<()> {
+        let data = client.post("/tasks/bulk", body).await?;
+        self.reload(client).await?;
+        if let Some(err) = data["results"].as_array().into_iter().flatten().find_map(|r| r["error"].as_str()) {
+            anyhow::bail!("{err}");
+        }
+        Ok(())
+    }
+}
+
+// Line editing for the prompts: Some(true) on Enter, Some(false) on Esc
+fn edit_text(text: &mut String, key: KeyEvent) -> Option<bool> {
+    match key.code {
+        KeyCode::Enter => return Some(true),
+        KeyCode::Esc => return Some(false),
+        KeyCode::Backspace => {
+            text.pop();
+        }
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => text.push(c),
+        _ => {}
+    }
+    None
+}
+
+fn draw(f: &mut Frame, app: &App) {
+    let [header, body, footer] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(f.area());
+    let [left, middle, right] = Layout::horizontal([Constraint::Ratio(1, 3); 3]).areas(body);
+    let [ui, nui] = Layout::vertical([Constraint::Ratio(1, 2); 2]).areas(left);
+    let [uni, nun] = Layout::vertical([Constraint::Ratio(1, 2); 2]).areas(middle);
+    for (col, area) in [ui, uni, right, nui, nun].into_iter().enumerate() {
+        draw_column(f, app, col, area);
+    }
+
+    let mut title = vec![Span::styled("Eisenhower Matrix", Style::new().add_modifier(Modifier::BOLD)), Span::raw(format!("  {}", app.board_name))];
+    if !app.filter.is_empty() {
+        title.push(Span::styled(format!("  search: {}", app.filter), Style::new().fg(Color::Yellow)));
+    }
+    f.render_widget(Line::from(title), header);
+    f.render_widget(Line::from("? help").right_aligned().style(Style::new().fg(Color::DarkGray)), header);
+
+    let cursor = "\u{258f}";
+    let bar = match &app.mode {
+        _ if !app.status.is_empty() => Line::from(app.status.as_str()).style(Style::new().fg(Color::Red)),
+        Mode::Browse => Line::from("arrows move  space pick up  e edit  a add  n quick-add  x done  1-4/t send  / search  q quit").style(Style::new().fg(Color::DarkGray)),
+        Mode::Carry { .. } => Line::from("carrying: arrows move the card, space/enter drops it, esc puts it back").style(Style::new().fg(Color::Yellow)),
+        Mode::Edit { .. } => Line::from("editing title: enter saves, esc cancels").style(Style::new().fg(Color::Yellow)),
+        Mode::Add { quick: false, text } => Line::from(format!("Add to {}: {text}{cursor}", COLUMNS[app.focus].1)),
+        Mode::Add { quick: true, text } => Line::from(format!("Quick add (!u !i @today #tag due:fri): {text}{cursor}")),
+        Mode::Search => Line::from(format!("/{}{cursor}", app.filter)),
+        Mode::ConfirmDelete { title, .. } => Line::from(format!("Delete \"{title}\"? y/n")).style(Style::new().fg(Color::Red)),
+        Mode::Help => Line::from("press any key to close"),
+    };
+    f.render_widget(bar, footer);
+
+    if matches!(app.mode, Mode::Help) {
+        draw_help(f);
+    }
+}
+
+fn draw_column(f: &mut Frame, app: &App, col: usize, area: Rect) {
+    let focused = col == app.focus;
+    let tasks = app.visible(col);
+    let today = Local::now().date_naive().to_string();
+    let items: Vec<ListItem> = tasks
+        .iter()
+        .map(|t| {
+            let title = match &app.mode {
+                Mode::Edit { id, text } if *id == t.id => Line::from(vec![Span::raw(text.clone()), Span::styled("\u{258f}", Style::new().fg(Color::Yellow))]),
+                _ => Line::from(t.title.clone()),
+            };
+            let mut meta = Vec::new();
+            for tag in &t.tags {
+                meta.push(Span::styled(format!("#{tag} "), Style::new().fg(Color::Cyan)));
+            }
+            if let Some(due) = &t.due_date {
+                let overdue = *due < today;
+                meta.push(Span::styled(format!("due {due} "), Style::new().fg(if overdue { Color::Red } else { Color::Gray })));
+            }
+            if let Some(who) = &t.assignee {
+                meta.push(Span::styled(format!("@{who}"), Style::new().fg(Color::DarkGray)));
+            }
+            let mut lines = vec![title];
+            if !meta.is_empty() {
+                lines.push(Line::from(meta));
+            }
+            ListItem::new(lines)
+        })
+        .collect();
+
+    let (label, colour) = (COLUMNS[col].1, COLOURS[col]);
+    let mut block = Block::bordered()
+        .title(format!(" {} ({}) ", label, tasks.len()))
+        .border_style(Style::new().fg(colour));
+    if focused {
+        block = block.border_type(BorderType::Thick).title_style(Style::new().add_modifier(Modifier::BOLD));
+    }
+    let highlight = match app.mode {
+        _ if !focused => Style::new(),
+        Mode::Carry { .. } => Style::new().bg(Color::Yellow).fg(Color::Black),
+        _ => Style::new().bg(colour).fg(Color::White),
+    };
+    let list = List::new(items).block(block).highlight_style(highlight).highlight_symbol(if focused { "> " } else { "  " });
+    let mut state = ListState::default().with_selected((!tasks.is_empty()).then_some(app.cursor[col]));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_help(f: &mut Frame) {
+    let [area] = Layout::horizontal([Constraint::Length(76)]).flex(Flex::Center).areas(f.area());
+    let [area] = Layout::vertical([Constraint::Length(HELP.len() as u16 + 2)]).flex(Flex::Center).areas(area);
+    let lines: Vec<Line> = HELP
+        .iter()
+        .map(|(keys, what)| Line::from(vec![Span::styled(format!("{keys:>14}  "), Style::new().fg(Color::Yellow)), Span::raw(*what)]))
+        .collect();
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Keyboard ")), area);
+}
@@ -0,0 +1,264 @@
+// Cross-site request forgery checks for everything behind basic auth. Browsers
+// attach Basic credentials to requests any site makes, so a mutation must also
+// come from this host (when the browser says where it came from) and carry a
+// token only our own pages and the CLI can read: the X-CSRF-Token header, or a
+// csrf_token field in a plain form post.
+//
+// Each page load (or GET /csrf) starts a session with its own token: an expiry
+// time and a random nonce, signed together with the username under a key drawn
+// at startup. Tokens are checked without any server-side state; one stops
+// working when it expires, when used by another user, or when the server
+// restarts.
+
+use std::{sync::LazyLock, time::Duration};
+
+use axum::{
+    body::{self, Body},
+    extract::{Extension, Request},
+    http::{header, HeaderMap, Method},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::warn;
+
+use crate::{
+    error::{AppError, AppResult},
+    CurrentUser,
+};
+
+pub const HEADER: &str = "X-CSRF-Token";
+pub const FIELD: &str = "csrf_token";
+const MAX_FORM_BYTES: usize = 64 * 1024;
+const LIFETIME: Duration = Duration::from_secs(12 * 60 * 60);
+
+static KEY: LazyLock<[u8; 32]> = LazyLock::new(rand::random);
+
+fn mac(username: &str, expires: i64, nonce: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&*KEY).expect("hmac takes any key length");
+    // The username can't hold '\n', so the parts can't run into each other
+    mac.update(format!("{username}\n{expires}\n{nonce}").as_bytes());
+    mac
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+// "<expires>.<nonce>.<signature>"
+fn issue(username: &str, now: i64) -> String {
+    let expires = now + LIFETIME.as_secs() as i64;
+    let nonce = hex::encode(rand::random::<[u8; 16]>());
+    let signature = hex::encode(mac(username, expires, &nonce).finalize().into_bytes());
+    format!("{expires}.{nonce}.{signature}")
+}
+
+fn valid_at(username: &str, token: &str, now: i64) -> bool {
+    let mut parts = token.splitn(3, '.');
+    let (Some(expires), Some(nonce), Some(signature)) = (parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+    let Ok(expires) = expires.parse::<i64>() else {
+        return false;
+    };
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    now < expires && mac(username, expires, nonce).verify_slice(&signature).is_ok()
+}
+
+/// A fresh token for a new session of `username`.
+pub fn token(username: &str) -> String {
+    issue(username, now())
+}
+
+fn valid(username: &str, token: &str) -> bool {
+    valid_at(username, token, now())
+}
+
+// GET /csrf: the caller's token, for clients that aren't served our pages
+pub async fn show(Extension(user): Extension<CurrentUser>) -> Response {
+    Json(serde_json::json!({ "token": token(&user.0) })).into_response()
+}
+
+pub async fn check(Extension(user): Extension<CurrentUser>, req: Request, next: Next) -> AppResult {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(next.run(req).await);
+    }
+    if !same_origin(req.headers(), req.uri().authority().map(|a| a.as_str())) {
+        warn!(user = %user.0, path = %req.uri().path(), "cross-origin request refused");
+        return Err(AppError::Forbidden("cross-origin request refused"));
+    }
+    let header = req.headers().get(HEADER).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let (req, sent) = match header {
+        Some(t) => (req, Some(t)),
+        None => form_token(req).await?,
+    };
+    if !sent.is_some_and(|t| valid(&user.0, &t)) {
+        warn!(user = %user.0, path = %req.uri().path(), "missing or wrong CSRF token");
+        return Err(AppError::Forbidden("missing or expired CSRF token; reload the page"));
+    }
+    Ok(next.run(req).await)
+}
+
+// The csrf_token field of a url-encoded form, handing back a request with the body put back
+async fn form_token(req: Request) -> AppResult<(Request, Option<String>)> {
+    let is_form = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+    if !is_form {
+        return Ok((req, None));
+    }
+    let (parts, body) = req.into_parts();
+    let bytes = body::to_bytes(body, MAX_FORM_BYTES)
+        .await
+        .map_err(|_| AppError::invalid("form is larger than 64 KiB"))?;
+    let token = form_urlencoded::parse(&bytes).find(|(k, _)| k == FIELD).map(|(_, v)| v.into_owned());
+    Ok((Request::from_parts(parts, Body::from(bytes)), token))
+}
+
+// Origin, or failing that Referer, must name the host the request was sent to:
+// the Host header, or over HTTP/2 (which has none) the URI's authority.
+// Requests with neither (curl, the CLI) are left to the token.
+fn same_origin(headers: &HeaderMap, uri_authority: Option<&str>) -> bool {
+    let source = headers.get(header::ORIGIN).or_else(|| headers.get(header::REFERER));
+    let Some(source) = source else {
+        return true;
+    };
+    let Some(authority) = source.to_str().ok().and_then(authority) else {
+        return false; // "null" or garbage
+    };
+    [header::HOST.as_str(), "x-forwarded-host"]
+        .iter()
+        .filter_map(|h| headers.get(*h)?.to_str().ok())
+        .chain(uri_authority)
+        .any(|host| host.eq_ignore_ascii_case(authority))
+}
+
+// "https://example.com:8080/path" -> "example.com:8080"
+fn authority(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    Some(&rest[..end]).filter(|a| !a.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderName, HeaderValue};
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        for (k, v) in pairs {
+            h.insert(HeaderName::from_bytes(k.as_bytes()).unwrap(), HeaderValue::from_str(v).unwrap());
+        }
+        h
+    }
+
+    #[test]
+    fn tokens_are_per_session_and_per_user() {
+        let (a, b) = (issue("alice", 1000), issue("alice", 1000));
+        assert_ne!(a, b);
+        assert!(valid_at("alice", &a, 1000) && valid_at("alice", &b, 1000));
+        assert!(!valid_at("bob", &a, 1000));
+    }
+
+    #[test]
+    fn tokens_expire() {
+        let t = issue("alice", 1000);
+        let expires = 1000 + LIFETIME.as_secs() as i64;
+        assert!(valid_at("alice", &t, expires - 1));
+        assert!(!valid_at("alice", &t, expires));
+    }
+
+    #[test]
+    fn tampered_tokens_fail() {
+        let t = issue("alice", 1000);
+        let (expires, rest) = t.split_once('.').unwrap();
+        let later = format!("{}.{rest}", expires.parse::<i64>().unwrap() + LIFETIME.as_secs() as i64);
+        assert!(!valid_at("alice", &later, 1000));
+        assert!(!valid_at("alice", &t.replace('.', ""), 1000));
+        assert!(!valid_at("alice", "", 1000));
+        assert!(!valid_at("alice", "1.2.zz", 1000));
+    }
+
+    #[test]
+    fn authority_of_urls() {
+        assert_eq!(authority("https://example.com:8080/path?q"), Some("example.com:8080"));
+        assert_eq!(authority("http://example.com"), Some("example.com"));
+        assert_eq!(authority("http://example.com?x"), Some("example.com"));
+        assert_eq!(authority("http://[::1]:8080/"), Some("[::1]:8080"));
+        assert_eq!(authority("null"), None);
+        assert_eq!(authority("https:///x"), None);
+    }
+
+    #[test]
+    fn same_origin_compares_origin_with_host() {
+        assert!(same_origin(&headers(&[("host", "tasks.example"), ("origin", "https://tasks.example")]), None));
+        assert!(same_origin(&headers(&[("host", "Tasks.Example:8443"), ("origin", "https://tasks.example:8443")]), None));
+        assert!(!same_origin(&headers(&[("host", "tasks.example"), ("origin", "https://evil.example")]), None));
+        assert!(!same_origin(&headers(&[("host", "tasks.example"), ("origin", "https://tasks.example:444")]), None));
+        assert!(!same_origin(&headers(&[("host", "tasks.example"), ("origin", "null")]), None));
+    }
+
+    #[test]
+    fn same_origin_falls_back_to_referer() {
+        assert!(same_origin(&headers(&[("host", "tasks.example"), ("referer", "https://tasks.example/?board=1")]), None));
+        assert!(!same_origin(&headers(&[("host", "tasks.example"), ("referer", "https://evil.example/tasks.example")]), None));
+    }
+
+    #[test]
+    fn same_origin_without_origin_or_referer_is_left_to_the_token() {
+        assert!(same_origin(&headers(&[("host", "tasks.example")]), None));
+    }
+
+    #[test]
+    fn same_origin_behind_a_proxy() {
+        let h = headers(&[("host", "backend:8080"), ("x-forwarded-host", "tasks.example"), ("origin", "https://tasks.example")]);
+        assert!(same_origin(&h, None));
+    }
+
+    #[test]
+    fn same_origin_over_http2_uses_the_uri_authority() {
+        // HTTP/2 has no Host header; the authority comes from the :authority pseudo-header
+        let h = headers(&[("origin", "https://tasks.example:8443")]);
+        assert!(same_origin(&h, Some("tasks.example:8443")));
+        assert!(!same_origin(&h, Some("other.example:8443")));
+        assert!(!same_origin(&h, None));
+    }
+
+    fn form(content_type: &str, body: &str) -> Request {
+        Request::builder()
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn form_token_reads_the_field_and_puts_the_body_back() {
+        let (req, token) = form_token(form("application/x-www-form-urlencoded", "name=B%26B&csrf_token=a.b.c")).await.unwrap();
+        assert_eq!(token.as_deref(), Some("a.b.c"));
+        let body = body::to_bytes(req.into_body(), MAX_FORM_BYTES).await.unwrap();
+        assert_eq!(&body[..], b"name=B%26B&csrf_token=a.b.c");
+    }
+
+    #[tokio::test]
+    async fn form_token_ignores_other_bodies() {
+        let (req, token) = form_token(form("application/json", r#"{"csrf_token":"a.b.c"}"#)).await.unwrap();
+        assert_eq!(token, None);
+        let body = body::to_bytes(req.into_body(), MAX_FORM_BYTES).await.unwrap();
+        assert_eq!(&body[..], br#"{"csrf_token":"a.b.c"}"#);
+        let (_, token) = form_token(form("application/x-www-form-urlencoded", "name=B")).await.unwrap();
+        assert_eq!(token, None);
+    }
+
+    #[tokio::test]
+    async fn form_token_refuses_huge_forms() {
+        let big = "x".repeat(MAX_FORM_BYTES + 1);
+        assert!(form_token(form("application/x-www-form-urlencoded", &big)).await.is_err());
+    }
+}
@@ -0,0 +1,113 @@
+// What a handler can fail with. Each kind has its own status code; `render`
+// (the outermost layer) turns the message into the body the caller expects:
+// JSON for /api and /hooks, an HTML fragment for htmx, plain text for the
+// page's own fetch() calls.
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use tracing::{debug, error};
+
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(&'static str),           // 400: a header that can't be read
+    NotFound(&'static str),             // 404
+    Forbidden(&'static str),            // 403
+    Conflict(String),                   // 409: the board changed under the request
+    PreconditionRequired(&'static str), // 428: an update without the version it is based on
+    Invalid(String),                    // 422: the request itself is wrong
+    Internal(anyhow::Error),            // 500: logged with its cause
+}
+
+pub type AppResult<T = Response> = Result<T, AppError>;
+
+impl AppError {
+    pub fn invalid(msg: impl Into<String>) -> AppError {
+        AppError::Invalid(msg.into())
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            AppError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Shown to the caller. An Internal error's text can hold SQL, constraint
+    // names or file paths, so it only goes to the log.
+    pub fn message(&self) -> String {
+        match self {
+            AppError::BadRequest(m) | AppError::NotFound(m) | AppError::Forbidden(m) | AppError::PreconditionRequired(m) => {
+                m.to_string()
+            }
+            AppError::Conflict(m) | AppError::Invalid(m) => m.clone(),
+            AppError::Internal(_) => "internal error".to_string(),
+        }
+    }
+}
+
+// Lets handlers use `?` on sqlx and anyhow results
+impl<E: Into<anyhow::Error>> From<E> for AppError {
+    fn from(e: E) -> AppError {
+        AppError::Internal(e.into())
+    }
+}
+
+// Message left on the response for `render`
+#[derive(Clone)]
+struct ErrorMessage(String);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        match &self {
+            AppError::Internal(e) => error!(error = format!("{e:#}"), "request failed"),
+            other => debug!(status = %other.status(), "{}", other.message()),
+        }
+        let message = self.message();
+        let mut res = (self.status(), message.clone()).into_response();
+        res.extensions_mut().insert(ErrorMessage(message));
+        res
+    }
+}
+
+pub async fn render(req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let json = path.starts_with("/api/") || path.starts_with("/hooks/");
+    let htmx = req.headers().contains_key("HX-Request");
+    let mut res = next.run(req).await;
+    let Some(ErrorMessage(message)) = res.extensions_mut().remove::<ErrorMessage>() else {
+        return res;
+    };
+    let status = res.status();
+    if json {
+        (status, Json(serde_json::json!({ "error": message }))).into_response()
+    } else if htmx {
+        match crate::views::html(&crate::views::ErrorFragment { message: &message }) {
+            Ok(html) => (status, html).into_response(),
+            Err(_) => res,
+        }
+    } else {
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_errors_keep_their_cause_out_of_the_response() {
+        let e = AppError::from(anyhow::anyhow!("UNIQUE constraint failed: tasks.external_id").context("could not add task"));
+        assert_eq!(e.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(e.message(), "internal error");
+        assert_eq!(AppError::invalid("title is required").message(), "title is required");
+    }
+}
@@ -1,29 +1,55 @@
 use axum::{
-    extract::{Form, Json, Path, State},
-    http::{Request, StatusCode, header},
-    response::{Html, IntoResponse, Response},
+    extract::{ConnectInfo, Extension, Form, Json, Path, Query, State},
+    http::{HeaderMap, HeaderValue, Request, StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::{get, post, patch},
     Router,
     middleware::{self, Next},
 };
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::Parser;
 use serde::{Deserialize, Serialize};
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
-use sqlx::Row;
-use std::{collections::BTreeMap, net::SocketAddr};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc, time::Duration};
 use tower_http::services::ServeDir;
-use tracing::{info, Level};
+use tracing::{info, warn};
 use tracing_subscriber::FmtSubscriber;
 
+mod admin;
+mod backups;
+mod config;
+mod csrf;
+mod error;
+mod password;
+mod quick_add;
+mod smtp;
+mod sort_key;
+mod storage;
+mod throttle;
+mod tls;
+mod validate;
+mod views;
+mod webhooks;
+
+use anyhow::Context;
+use error::{AppError, AppResult};
+use storage::{Db, Storage, Transaction};
+
 
 #[derive(Clone)]
 struct AppState {
-    pool: SqlitePool,
+    db: Db,
+    mail_domain: Option<String>, // set when the SMTP receiver is running
+    logins: Arc<throttle::Throttle>,
+    verified: Arc<password::Cache>, // recent good logins, so Argon2 doesn't run on every request
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+// Stored as the variant name in a TEXT column; an unknown name fails to decode
+#[derive(Debug, Clone, Copy, Serialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT")]
+#[allow(clippy::enum_variant_names)]
 enum TaskType {
     UrgentImportant,
     UrgentNotImportant,
@@ -41,6 +67,15 @@ impl TaskType {
             Bucket::Today => TaskType::UrgentImportant, // default when adding directly to Today
         }
     }
+    // The quadrant a task of this type belongs in
+    fn bucket(&self) -> Bucket {
+        match self {
+            TaskType::UrgentImportant => Bucket::UrgentImportant,
+            TaskType::UrgentNotImportant => Bucket::UrgentNotImportant,
+            TaskType::NotUrgentImportant => Bucket::NotUrgentImportant,
+            TaskType::NotUrgentNotImportant => Bucket::NotUrgentNotImportant,
+        }
+    }
     fn as_str(&self) -> &'static str {
         match self {
             TaskType::UrgentImportant => "UrgentImportant",
@@ -51,7 +86,8 @@ impl TaskType {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT")]
 enum Bucket {
     UrgentImportant,
     UrgentNotImportant,
@@ -70,371 +106,481 @@ impl Bucket {
             Bucket::Today => "Today",
         }
     }
+    fn label(&self) -> &'static str {
+        match self {
+            Bucket::UrgentImportant => "Urgent & Important",
+            Bucket::UrgentNotImportant => "Urgent & Not Important",
+            Bucket::NotUrgentImportant => "Not Urgent & Important",
+            Bucket::NotUrgentNotImportant => "Not Urgent & Not Important",
+            Bucket::Today => "Today's Tasks",
+        }
+    }
+    // id of the column's <ul> in render_index
+    fn list_id(&self) -> &'static str {
+        match self {
+            Bucket::UrgentImportant => "list-UI",
+            Bucket::UrgentNotImportant => "list-UNI",
+            Bucket::NotUrgentImportant => "list-NUI",
+            Bucket::NotUrgentNotImportant => "list-NUN",
+            Bucket::Today => "list-TODAY",
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
 struct Task {
     id: i64,
     title: String,
     task_type: TaskType, // color source for 'Today'
     bucket: Bucket,      // actual column the task is in
     completed: bool,
-    position: i64,
+    sort_key: String, // fractional key ordering the task within its bucket
+    board_id: i64,
+    created_by: Option<String>,
+    updated_by: Option<String>,
+    assignee: Option<String>,
+    #[sqlx(try_from = "storage::TagList")]
+    tags: Vec<String>,
+    due_date: Option<NaiveDate>,
+    notes: Option<String>,
+    external_id: Option<String>, // id in the tool that created it via /hooks/tasks
+    version: i64, // bumped on every change, exposed as the task's ETag
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+struct Board {
+    id: i64,
+    name: String,
+    owner: String,
+    role: Role, // the current user's role on this board
+}
+
+// Access level on a board; ordered so `role >= Role::Editor` means "may modify"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+fn parse_role(s: &str) -> Option<Role> {
+    Some(match s {
+        "viewer" => Role::Viewer,
+        "editor" => Role::Editor,
+        "owner" => Role::Owner,
+        _ => return None,
+    })
+}
+
+// Username of the authenticated caller, inserted by `basic_auth`
+#[derive(Debug, Clone)]
+struct CurrentUser(String);
+
+impl CurrentUser {
+    // The account configured through EISENHOWER_USERNAME manages the other users
+    fn is_admin(&self) -> bool {
+        self.0 == admin_username()
+    }
+}
+
+#[derive(Parser)]
+#[command(version, about = "Eisenhower matrix server; runs `serve` when no command is given")]
+struct Cli {
+    #[command(flatten)]
+    flags: config::Flags,
+    #[command(subcommand)]
+    command: Option<admin::Command>,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    config::set(config::load(&cli.flags)?);
+    let config = config::get();
+
     let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
+        .with_max_level(config.log_level)
         .finish();
     tracing::subscriber::set_global_default(subscriber).ok();
 
-    let db_url = "sqlite://tasks.db";
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(db_url)
-        .await?;
+    match cli.command.unwrap_or(admin::Command::Serve) {
+        admin::Command::Serve => serve(storage::connect(&config.database).await?).await,
+        // Works on the file, not through a pool
+        admin::Command::Restore { from, latest } => admin::restore(&config.database, from, latest).await,
+        command => admin::run(&storage::connect(&config.database).await?, command).await,
+    }
+}
+
+async fn serve(db: Db) -> anyhow::Result<()> {
+    // Keep sort keys short: once at startup, then periodically
+    let rebalance_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(10 * 60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = rebalance_sort_keys(&*rebalance_db).await {
+                warn!(error = %e, "sort key rebalance failed");
+            }
+        }
+    });
 
-    // Run migrations from ./migrations
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    let config = config::get();
+    let mut mail_domain = None;
+    if let Some(email_in) = &config.email_in {
+        let smtp_db = db.clone();
+        let (addr, smtp_domain) = (email_in.bind, email_in.domain.clone());
+        tokio::spawn(async move {
+            if let Err(e) = smtp::serve(smtp_db, addr, smtp_domain).await {
+                warn!(error = %e, "smtp receiver stopped");
+            }
+        });
+        mail_domain = Some(email_in.domain.clone());
+    }
 
-    let state = AppState { pool };
+    if config.features.webhooks {
+        tokio::spawn(webhooks::run(db.clone()));
+    }
+    if let Some(backup) = &config.backup {
+        tokio::spawn(backups::run(db.clone(), backup));
+    }
+    if config.auth.mode == config::AuthMode::None {
+        warn!(addr = %config.bind, "authentication is off: anyone who can reach the server is {}", config.auth.admin_username);
+    }
+    if config.auth.default_password() {
+        warn!(
+            addr = %config.bind,
+            "{} still has the default password; set EISENHOWER_PASSWORD (or auth.admin_password) before exposing the server",
+            config.auth.admin_username
+        );
+    }
 
-    // Read port from env
-    let port: u16 = std::env::var("PORT").ok().and_then(|s| s.parse().ok()).unwrap_or(8080);
+    let state = AppState { db, mail_domain, logins: Arc::default(), verified: Arc::default() };
 
-    let app = Router::new()
+    let mut routes = Router::new()
         .route("/", get(index))
         .route("/tasks", post(add_task))
         .route("/tasks/{id}/delete", post(delete_task))
         .route("/tasks/{id}/toggle", post(toggle_task))
         .route("/tasks/{id}", patch(update_task))
-        .route("/reorder", post(reorder_bucket))
+        .route("/tasks/bulk", post(bulk_tasks))
+        .route("/tasks/preview", get(preview_task))
         .route("/move", post(move_task))
         .route("/completed", get(completed_tasks)) // Route for completed tasks
+        .route("/tasks/{id}/boards", get(task_board_picker))
+        .route("/tasks/{id}/board", post(move_task_to_board))
+        .route("/tasks/{id}/assignees", get(task_assignee_picker))
+        .route("/tasks/{id}/assign", post(assign_task))
+        .route("/boards", post(create_board))
+        .route("/boards/{id}/delete", post(delete_board))
+        .route("/boards/{id}/members", get(board_members).post(share_board))
+        .route("/boards/{id}/members/{username}/delete", post(unshare_board))
+        .route("/users", get(list_users).post(create_user))
+        .route("/inbox", get(inbox))
+        .route("/inbox/rotate", post(rotate_inbox))
+        .route("/backup/status", get(backup_status))
+        .route("/csrf", get(csrf::show));
+    // The Webhooks panel holds both outgoing hooks and incoming tokens
+    if config.features.webhooks || config.features.incoming {
+        routes = routes.route("/boards/{id}/webhooks", get(board_webhooks));
+    }
+    if config.features.webhooks {
+        routes = routes
+            .route("/boards/{id}/webhooks", post(create_webhook))
+            .route("/boards/{id}/webhooks/{hook_id}/delete", post(delete_webhook));
+    }
+    if config.features.incoming {
+        routes = routes
+            .route("/boards/{id}/tokens", post(create_incoming_token))
+            .route("/boards/{id}/tokens/{token_id}/delete", post(delete_incoming_token));
+    }
+    if config.features.api {
+        routes = routes
+            .route("/api/boards", get(api_boards))
+            .route("/api/tasks", get(api_tasks).post(api_add_task));
+    }
+    let mut app = routes
         .with_state(state.clone())
         .nest_service("/static", ServeDir::new("static"))
-        .layer(middleware::from_fn(basic_auth));
+        .layer(middleware::from_fn(csrf::check))
+        .layer(middleware::from_fn_with_state(state.clone(), basic_auth));
+    if config.features.incoming {
+        // Authenticated by its own bearer tokens rather than HTTP basic auth
+        app = app.merge(Router::new().route("/hooks/tasks", post(incoming_task)).with_state(state.clone()));
+    }
+    let app = app.layer(middleware::from_fn(error::render));
 
-    use tokio::net::TcpListener;
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    let listener = TcpListener::bind(addr).await?;
-    info!(?addr, "listening");
-    axum::serve(listener, app).await?;
+    let addr = config.bind;
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    if let Some(tls) = &config.tls {
+        let rustls = tls::load(tls).await?;
+        tokio::spawn(tls::watch(rustls.clone(), tls));
+        if let Some(redirect) = tls.redirect {
+            tokio::spawn(async move {
+                if let Err(e) = tls::redirect(redirect, addr.port()).await {
+                    warn!(error = %e, "http redirect listener stopped");
+                }
+            });
+        }
+        info!(?addr, "listening (https)");
+        axum_server::bind_rustls(addr, rustls).serve(app).await?;
+    } else {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        info!(?addr, "listening");
+        axum::serve(listener, app).await?;
+    }
 
     Ok(())
 }
 
-async fn index(State(state): State<AppState>) -> impl IntoResponse {
-    let groups = fetch_all_grouped(&state.pool).await.unwrap_or_default();
-    let html = render_index(groups);
-    Html(html)
-}
-
-fn render_index(groups: BTreeMap<&'static str, Vec<Task>>) -> String {
-    let ui = groups.get("UrgentImportant").cloned().unwrap_or_default().into_iter().filter(|t| !t.completed).collect::<Vec<_>>();
-    let uni = groups.get("UrgentNotImportant").cloned().unwrap_or_default().into_iter().filter(|t| !t.completed).collect::<Vec<_>>();
-    let nui = groups.get("NotUrgentImportant").cloned().unwrap_or_default().into_iter().filter(|t| !t.completed).collect::<Vec<_>>();
-    let nun = groups.get("NotUrgentNotImportant").cloned().unwrap_or_default().into_iter().filter(|t| !t.completed).collect::<Vec<_>>();
-    let today = groups.get("Today").cloned().unwrap_or_default().into_iter().filter(|t| !t.completed).collect::<Vec<_>>();
-
-            let s = format!(r#"<!DOCTYPE html>
-    <html lang="en">
-    <head>
-    <meta charset="utf-8"/>
-    <meta name="viewport" content="width=device-width, initial-scale=1"/>
-    <title>Eisenhower Matrix</title>
-    <link rel="stylesheet" href="/static/style.css">
-    <script src="https://unpkg.com/htmx.org@1.9.10"></script>
-    <script src="https://cdn.jsdelivr.net/npm/sortablejs@1.15.2/Sortable.min.js"></script>
-    </head>
-    <body>
-    <div class="matrix-scroll">
-        <div class="header">
-        <strong>Eisenhower Matrix</strong>
-        <span class="small muted">Rust + Axum + SQLx • SQLite file: tasks.db</span>
-    <button id="refresh-btn" style="float:right; margin-left:8px;">Refresh</button>
-    <button id="show-completed-btn" style="float:right; margin-left:16px;">Completed Tasks</button>
-    </div>
-    <div class="grid">
-        <section class="column ui">
-                <div class="column-title"><div>Urgent & Important</div><span class="badge">Add / Drag</span></div>
-                {}
-        </section>
-        <section class="column uni">
-                <div class="column-title"><div>Urgent & Not Important</div><span class="badge">Add / Drag</span></div>
-                {}
-        </section>
-        <section class="column today">
-                <div class="column-title"><div>Today's Tasks</div><span class="badge">Drag from any column</span></div>
-                {}
-        </section>
-        <section class="column nui">
-                <div class="column-title"><div>Not Urgent & Important</div><span class="badge">Add / Drag</span></div>
-                {}
-        </section>
-        <section class="column nun">
-                <div class="column-title"><div>Not Urgent & Not Important</div><span class="badge">Add / Drag</span></div>
-                {}
-        </section>
-    </div>
-    <div id="completed-panel" class="completed-panel" style="display:none;">
-        <div class="completed-panel-content">
-            <button id="close-completed-btn" style="float:right;">Close</button>
-            <h2>Completed Tasks</h2>
-            <div id="completed-tasks-list" hx-get="/completed" hx-trigger="revealed" hx-swap="innerHTML"></div>
-        </div>
-    </div>
-</div>
-<script>
-    function bootSortable(listId, bucket){{
-        const el = document.getElementById(listId);
-        if(!el) return;
-        new Sortable(el, {{
-            animation: 150,
-            group: 'matrix',
-            delay: 350,
-            delayOnTouchOnly: true,
-            onEnd: function(evt){{
-                const ids = Array.from(el.querySelectorAll('li.task')).map(li => Number(li.dataset.id));
-                fetch('/reorder', {{
-                    method: 'POST',
-                    headers: {{'Content-Type':'application/json'}},
-                    body: JSON.stringify({{ bucket: bucket, orderedIds: ids }})
-                    }}).then(() => {{
-                    if(evt.item && evt.to){{
-                        const newBucket = evt.to.dataset.bucket;
-                        fetch('/move', {{
-                            method:'POST',
-                            headers:{{'Content-Type':'application/json'}},
-                            body: JSON.stringify({{ id: Number(evt.item.dataset.id), bucket: newBucket, index: evt.newIndex }})
-                        }})
-                        .then(response => response.text())
-                        .then(html => {{
-                            // Replace the moved element's HTML with the updated HTML from the server
-                            // and re-initialize HTMX on the new element
-                            const temp = document.createElement('div');
-                            temp.innerHTML = html;
-                            const newElem = temp.firstElementChild;
-                            evt.item.replaceWith(newElem);
-                            if(window.htmx && newElem) {{
-                                window.htmx.process(newElem);
-                            }}
-    }});
-}}
-}});
-}}
-}});
-}}
-        document.addEventListener('DOMContentLoaded', function(){{
-                bootSortable('list-UI', 'UrgentImportant');
-                bootSortable('list-UNI', 'UrgentNotImportant');
-                bootSortable('list-NUI', 'NotUrgentImportant');
-                bootSortable('list-NUN', 'NotUrgentNotImportant');
-                bootSortable('list-TODAY', 'Today');
-}});
-
-        // Preserve horizontal scroll position across HTMX reloads
-        let lastScrollX = 0;
-        document.body.addEventListener('htmx:beforeSwap', function() {{
-            const ms = document.querySelector('.matrix-scroll');
-            if (ms) lastScrollX = ms.scrollLeft;
-}});
-        document.body.addEventListener('htmx:afterSwap', function() {{
-            const ms = document.querySelector('.matrix-scroll');
-            if (ms) ms.scrollLeft = lastScrollX;
-}});
-        document.getElementById('show-completed-btn').onclick = function() {{
-            document.getElementById('completed-panel').style.display = 'block';
-            document.getElementById('completed-tasks-list').dispatchEvent(new Event('revealed'));
-}};
-            document.getElementById('refresh-btn').onclick = function() {{
-                const ms = document.querySelector('.matrix-scroll');
-                if (ms) {{
-                    sessionStorage.setItem('matrixScrollX', ms.scrollLeft);
-                }}
-                window.location.reload();
-            }};
-        document.getElementById('close-completed-btn').onclick = function() {{
-            const ms = document.querySelector('.matrix-scroll');
-            if (ms) {{
-                sessionStorage.setItem('matrixScrollX', ms.scrollLeft);
-}}
-            document.getElementById('completed-panel').style.display = 'none';
-            window.location.reload();
-}};
-        // Restore horizontal scroll position after reload
-        document.addEventListener('DOMContentLoaded', function() {{
-            const ms = document.querySelector('.matrix-scroll');
-            const scrollX = sessionStorage.getItem('matrixScrollX');
-            if (ms && scrollX) {{
-                ms.scrollLeft = parseInt(scrollX, 10);
-                sessionStorage.removeItem('matrixScrollX');
-}}
-}});
-    // No custom event listeners needed; Undo button uses hx-on::afterRequest for reload
-</script>
-</body></html>
-"#,
-    render_column("UrgentImportant", "list-UI", &ui),
-    render_column("UrgentNotImportant", "list-UNI", &uni),
-    render_column("Today", "list-TODAY", &today),
-    render_column("NotUrgentImportant", "list-NUI", &nui),
-    render_column("NotUrgentNotImportant", "list-NUN", &nun),
-);
-    s
-}
-
-fn render_column(bucket: &str, list_id: &str, tasks: &Vec<Task>) -> String {
-    let mut html = String::new();
-    html.push_str(&format!(r#"<ul class="tasklist" id="{}" data-bucket="{}">"#, list_id, bucket));
-    for t in tasks {
-        html.push_str(&render_task(t));
-    }
-    html.push_str("</ul>");
-    html.push_str(&format!(r#"
-    <form class='add-form' hx-post='/tasks' hx-target='#{0}' hx-swap='beforeend' hx-on::after-request="this.reset()">
-  <input type='hidden' name='bucket' value='{1}'/>
-  <input type='text' name='title' placeholder='Add new task here...' autocomplete='off'>
-  <button type='submit'>Add</button>
-</form>
-"#, list_id, bucket));
-    html
-}
-
-fn render_task(t: &Task) -> String {
-    let chip = match t.bucket {
-        Bucket::Today => match t.task_type {
-            TaskType::UrgentImportant => "color-UI",
-            TaskType::UrgentNotImportant => "color-UNI",
-            TaskType::NotUrgentImportant => "color-NUI",
-            TaskType::NotUrgentNotImportant => "color-NUN",
-        },
-        Bucket::UrgentImportant => "color-UI",
-        Bucket::UrgentNotImportant => "color-UNI",
-        Bucket::NotUrgentImportant => "color-NUI",
-        Bucket::NotUrgentNotImportant => "color-NUN",
+#[derive(Deserialize)]
+struct BoardQuery { board: Option<i64> }
+
+async fn index(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Query(q): Query<BoardQuery>,
+) -> AppResult {
+    let boards = user_boards(&*state.db, &user.0).await.context("could not list boards")?;
+    let current = pick_board(&boards, q.board)?;
+    let groups = fetch_all_grouped(&*state.db, current.id, &user.0).await.context("could not load tasks")?;
+    Ok(render_index(groups, &boards, &current, &user, state.mail_domain.is_some())?.into_response())
+}
+
+// The requested board among the user's, or their first one
+fn pick_board(boards: &[Board], id: Option<i64>) -> AppResult<Board> {
+    match id {
+        Some(id) => boards.iter().find(|b| b.id == id).cloned().ok_or(AppError::NotFound("board not found")),
+        None => boards.first().cloned().context("no board").map_err(AppError::from),
+    }
+}
+
+fn render_index(groups: BTreeMap<&'static str, Vec<Task>>, boards: &[Board], current: &Board, user: &CurrentUser, mail_enabled: bool) -> AppResult<Html<String>> {
+    let column = |bucket: Bucket, class, badge| views::Column {
+        bucket,
+        class,
+        badge,
+        tasks: groups.get(bucket.as_str()).into_iter().flatten().filter(|t| !t.completed).cloned().collect(),
     };
-    let title = html_escape(&t.title);
-    // Use icons for Done (check square) and Undo (circular arrow)
-    let done_button = if t.completed {
-        // Undo: SVG undo background
-        format!("<button class='undo-btn' hx-post='/tasks/{}/toggle' hx-swap='outerHTML' hx-target='closest li.task' hx-on::afterSwap='window.location.reload()' title='Undo'><span class='svg-undo'></span></button>", t.id)
-    } else {
-        // Done: SVG checkmark background
-        format!("<button class='done-btn' hx-post='/tasks/{}/toggle' hx-swap='outerHTML' hx-target='closest li.task' hx-on::afterSwap='window.location.reload()' title='Done'><span class='svg-check'></span></button>", t.id)
+    let features = &config::get().features;
+    views::html(&views::Index {
+        boards,
+        current,
+        columns: vec![
+            column(Bucket::UrgentImportant, "ui", "Add / Drag"),
+            column(Bucket::UrgentNotImportant, "uni", "Add / Drag"),
+            column(Bucket::Today, "today", "Drag from any column"),
+            column(Bucket::NotUrgentImportant, "nui", "Add / Drag"),
+            column(Bucket::NotUrgentNotImportant, "nun", "Add / Drag"),
+        ],
+        read_only: current.role < Role::Editor,
+        is_admin: user.is_admin(),
+        csrf_token: csrf::token(&user.0),
+        database: user.is_admin().then(|| {
+            let db = &config::get().database;
+            let kind = if db.sqlite_path().is_some() { "SQLite file" } else { "Postgres" };
+            format!("{kind}: {db}")
+        }),
+        mail_enabled,
+        webhooks_enabled: features.webhooks || features.incoming,
+    })
+}
+
+// Boards owned by or shared with `username` (owned first), creating a default
+// one the first time a user shows up. Tasks from before boards existed were
+// all the admin's, so only the admin's default board adopts them.
+async fn user_boards(db: &dyn Storage, username: &str) -> anyhow::Result<Vec<Board>> {
+    let mut boards = db.boards_for(username).await?;
+    if !boards.iter().any(|b| b.role == Role::Owner) {
+        let mut tx = db.begin().await?;
+        let id = tx.create_board("Main", username).await?;
+        if username == admin_username() {
+            tx.adopt_orphan_tasks(id).await?;
+        }
+        tx.commit().await?;
+        boards.insert(0, Board { id, name: "Main".to_string(), owner: username.to_string(), role: Role::Owner });
+    }
+    Ok(boards)
+}
+
+// Resolve the caller's role on a board, answering 404 when they cannot see it
+// at all and 403 when they can see it but not at the `needed` level.
+async fn require_board_role(db: &dyn Storage, user: &CurrentUser, board_id: i64, needed: Role) -> AppResult<Role> {
+    match db.board_role(&user.0, board_id).await? {
+        None => Err(AppError::NotFound("board not found")),
+        Some(role) if role < needed => Err(AppError::Forbidden("insufficient permissions on this board")),
+        Some(role) => Ok(role),
+    }
+}
+
+// Like `require_board_role` for the board a task lives on; returns that board's id.
+async fn require_task_role(db: &dyn Storage, user: &CurrentUser, task_id: i64, needed: Role) -> AppResult<i64> {
+    let Some(board_id) = db.task_board(task_id).await? else {
+        return Err(AppError::NotFound("task not found"));
     };
-    let delete_button = format!("<button class='delete-btn' hx-post='/tasks/{}/delete' hx-target='closest li.task' hx-swap='outerHTML' title='Delete'><span class='svg-x'></span></button>", t.id);
-    format!(r#"<li class="task" data-id="{}">
-        <div class="color-chip {}"></div>
-        <div class="text" contenteditable="true"
-                 onblur="fetch('/tasks/{}', {{method:'PATCH', headers:{{'Content-Type':'application/json'}}, body: JSON.stringify({{title:this.innerText}})}})">{}</div>
-        <div class="controls">
-        {}{}
-        </div>
-    </li>"#, t.id, chip, t.id, title, done_button, delete_button)
-}
-
-fn html_escape(s: &str) -> String {
-    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
-}
-
-async fn fetch_all_grouped(pool: &SqlitePool) -> anyhow::Result<BTreeMap<&'static str, Vec<Task>>> {
-    let rows = sqlx::query(
-        r#"SELECT id, title, task_type, bucket,
-                  completed, position, created_at, updated_at
-           FROM tasks
-           ORDER BY bucket, position ASC"#)
-        .fetch_all(pool)
-        .await?;
-
-    use sqlx::Row;
+    require_board_role(db, user, board_id, needed).await.map(|_| board_id)
+}
+
+// Group a board's tasks by the column they show in for `viewer`: Today holds the
+// viewer's own day plan (plus unassigned legacy Today tasks), while tasks on
+// someone else's Today list stay visible in their quadrant.
+async fn fetch_all_grouped(db: &dyn Storage, board_id: i64, viewer: &str) -> anyhow::Result<BTreeMap<&'static str, Vec<Task>>> {
     let mut map: BTreeMap<&'static str, Vec<Task>> = BTreeMap::new();
-    for r in rows {
-        let tp = match r.get::<String, _>("task_type").as_str() {
-            "UrgentImportant" => TaskType::UrgentImportant,
-            "UrgentNotImportant" => TaskType::UrgentNotImportant,
-            "NotUrgentImportant" => TaskType::NotUrgentImportant,
-            _ => TaskType::NotUrgentNotImportant,
+    for task in db.tasks(board_id).await? {
+        let column = if on_others_plan(&task, viewer) {
+            task.task_type.bucket().as_str()
+        } else {
+            task.bucket.as_str()
         };
-        let bucket = match r.get::<String, _>("bucket").as_str() {
-            "UrgentImportant" => Bucket::UrgentImportant,
-            "UrgentNotImportant" => Bucket::UrgentNotImportant,
-            "NotUrgentImportant" => Bucket::NotUrgentImportant,
-            "NotUrgentNotImportant" => Bucket::NotUrgentNotImportant,
-            _ => Bucket::Today,
-        };
-        let task = Task {
-            id: r.get("id"),
-            title: r.get("title"),
-            task_type: tp,
-            bucket,
-            completed: r.get::<i64, _>("completed") != 0,
-            position: r.get("position"),
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-        };
-        map.entry(bucket.as_str()).or_default().push(task);
+        map.entry(column).or_default().push(task);
+    }
+    // Others' day-plan tasks arrive in Today order; keep them after the quadrant's own tasks
+    for tasks in map.values_mut() {
+        tasks.sort_by_key(|t| on_others_plan(t, viewer));
     }
     Ok(map)
 }
 
+fn on_others_plan(t: &Task, viewer: &str) -> bool {
+    matches!(t.bucket, Bucket::Today) && t.assignee.as_deref().is_some_and(|a| a != viewer)
+}
+
 #[derive(Deserialize)]
 struct NewTask {
     title: String,
+    #[serde(default)]
     bucket: String,
+    board_id: i64,
+    quick: Option<String>, // set by the quick-add box: parse markers out of `title`
 }
 
 async fn add_task(
     State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
     Form(form): Form<NewTask>,
-) -> impl IntoResponse {
-    if form.title.trim().is_empty() {
-        return (StatusCode::BAD_REQUEST, "Title required").into_response();
-    }
-    let bucket = parse_bucket(&form.bucket).unwrap_or(Bucket::UrgentImportant);
-    let task_type = if matches!(bucket, Bucket::Today) {
-        TaskType::UrgentImportant
+) -> AppResult {
+    let draft = if form.quick.is_some() {
+        // Checked after the markers are taken out: "!u #work" alone has no title
+        let mut draft = TaskDraft::from_quick_add(quick_add::parse(&form.title, config::today()));
+        draft.title = validate::title("title", &draft.title)?;
+        draft
     } else {
-        TaskType::from_bucket(bucket)
+        TaskDraft::new(validate::title("title", &form.title)?, validate::bucket("bucket", &form.bucket)?)
     };
+    require_board_role(&*state.db, &user, form.board_id, Role::Editor).await?;
 
-    let max_pos: Option<(i64,)> = sqlx::query_as(
-        r#"SELECT COALESCE(MAX(position), 0) FROM tasks WHERE bucket = ?1"#,
-    )
-    .bind(bucket.as_str())
-    .fetch_optional(&state.pool)
-    .await
-    .ok()
-    .flatten();
+    let task = create_task(&*state.db, form.board_id, &user.0, draft).await.context("could not add task")?;
+    if form.quick.is_some() {
+        // The quick-add box isn't a column; send the card to the one it was classified into
+        return Ok((
+            [("HX-Retarget", format!("#{}", task.bucket.list_id())), ("HX-Reswap", "beforeend".to_string())],
+            views::html(&views::TaskCard { task: &task })?,
+        )
+            .into_response());
+    }
+    Ok(views::html(&views::TaskCard { task: &task })?.into_response())
+}
+
+// A task that hasn't been stored yet: everything but its board and author
+struct TaskDraft {
+    title: String,
+    bucket: Bucket,
+    task_type: TaskType,
+    tags: Vec<String>,
+    due_date: Option<NaiveDate>,
+    notes: Option<String>,
+    external_id: Option<String>,
+}
 
-    let pos = max_pos.map(|t| t.0 + 1).unwrap_or(1);
+impl TaskDraft {
+    fn new(title: String, bucket: Bucket) -> TaskDraft {
+        TaskDraft {
+            title,
+            bucket,
+            task_type: TaskType::from_bucket(bucket),
+            tags: Vec::new(),
+            due_date: None,
+            notes: None,
+            external_id: None,
+        }
+    }
 
-    let id = sqlx::query(
-        r#"INSERT INTO tasks(title, task_type, bucket, position) VALUES (?1, ?2, ?3, ?4)"#,
-    )
-    .bind(form.title.trim())
-    .bind(task_type.as_str())
-    .bind(bucket.as_str())
-    .bind(pos)
-    .execute(&state.pool)
-    .await
-    .unwrap()
-    .last_insert_rowid();
-
-    let task = Task {
-        id,
-        title: form.title.trim().to_string(),
-        task_type,
-        bucket,
-        completed: false,
-        position: pos,
-        created_at: Utc::now(),
-        updated_at: Utc::now(),
-    };
-    Html(render_task(&task)).into_response()
+    fn from_quick_add(q: quick_add::QuickAdd) -> TaskDraft {
+        TaskDraft {
+            bucket: q.bucket(),
+            task_type: q.task_type(),
+            title: q.title,
+            tags: q.tags,
+            due_date: q.due,
+            notes: None,
+            external_id: None,
+        }
+    }
+}
+
+// Store a new task at the end of its column on `board_id`
+async fn create_task(db: &dyn Storage, board_id: i64, username: &str, draft: TaskDraft) -> anyhow::Result<Task> {
+    let mut tx = db.begin().await?;
+    let key = append_sort_key(&mut *tx, board_id, draft.bucket).await?;
+    // Adding straight to Today puts the task on the author's own day plan
+    let assignee = matches!(draft.bucket, Bucket::Today).then(|| username.to_string());
+
+    let id = tx.insert_task(board_id, username, &draft, &key, assignee.as_deref()).await?;
+    for tag in &draft.tags {
+        tx.add_tag(id, tag).await?;
+    }
+    tx.commit().await?;
+
+    // Read it back so the timestamps are the ones the database stored
+    let task = db.task(id).await?.ok_or_else(|| anyhow::anyhow!("task {id} vanished after insert"))?;
+    webhooks::emit(db, "task.created", &task).await;
+    Ok(task)
+}
+
+#[derive(Deserialize)]
+struct PreviewQuery { title: Option<String> }
+
+// Live "where will this go" line under the quick-add box
+async fn preview_task(Query(q): Query<PreviewQuery>) -> AppResult<Html<String>> {
+    let text = q.title.unwrap_or_default();
+    if text.trim().is_empty() {
+        return Ok(Html(String::new()));
+    }
+    views::html(&views::Preview { q: quick_add::parse(&text, config::today()) })
+}
+
+fn parse_task_type(s: &str) -> Option<TaskType> {
+    Some(match s {
+        "UrgentImportant" => TaskType::UrgentImportant,
+        "UrgentNotImportant" => TaskType::UrgentNotImportant,
+        "NotUrgentImportant" => TaskType::NotUrgentImportant,
+        "NotUrgentNotImportant" => TaskType::NotUrgentNotImportant,
+        _ => return None,
+    })
+}
+
+// A Bucket name or its short form: "ui", "uni", "nui", "nun", "today"
+fn parse_quadrant(s: &str) -> Option<Bucket> {
+    parse_bucket(s).or(match s.to_lowercase().as_str() {
+        "ui" => Some(Bucket::UrgentImportant),
+        "uni" => Some(Bucket::UrgentNotImportant),
+        "nui" => Some(Bucket::NotUrgentImportant),
+        "nun" => Some(Bucket::NotUrgentNotImportant),
+        "today" => Some(Bucket::Today),
+        _ => None,
+    })
 }
 
 fn parse_bucket(s: &str) -> Option<Bucket> {
@@ -450,223 +596,1597 @@ fn parse_bucket(s: &str) -> Option<Bucket> {
 
 async fn delete_task(
     State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
     Path(id): Path<i64>,
-) -> impl IntoResponse {
-    sqlx::query(r#"DELETE FROM tasks WHERE id = ?1"#)
-        .bind(id)
-        .execute(&state.pool)
-        .await
-        .ok();
-    Html(String::new())
+) -> AppResult {
+    require_task_role(&*state.db, &user, id, Role::Editor).await?;
+    // Read before deleting: the webhook payload needs the task
+    let task = state.db.task(id).await?;
+    let mut tx = state.db.begin().await?;
+    tx.delete_task(id).await?;
+    tx.commit().await.context("could not delete task")?;
+    if let Some(task) = task {
+        webhooks::emit(&*state.db, "task.deleted", &task).await;
+    }
+    Ok(Html(String::new()).into_response())
 }
 
 async fn toggle_task(
     State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
     Path(id): Path<i64>,
-) -> impl IntoResponse {
-    let _ = sqlx::query(
-        r#"UPDATE tasks
-           SET completed = 1 - completed, updated_at = datetime('now')
-           WHERE id = ?1"#,
-    )
-    .bind(id)
-    .execute(&state.pool)
-    .await;
-    // use sqlx::Row;
-    if let Ok(Some(_r)) = sqlx::query(
-        r#"SELECT id FROM tasks WHERE id = ?1"#,
-    )
-    .bind(id)
-    .fetch_optional(&state.pool).await {
-        // Always remove the <li> from the current list; JS will reload as needed
-        return Html("").into_response();
-    }
-    (StatusCode::NOT_FOUND, "not found").into_response()
+) -> AppResult {
+    require_task_role(&*state.db, &user, id, Role::Editor).await?;
+    state.db.toggle_completed(id, &user.0).await.context("could not toggle task")?;
+    let task = state.db.task(id).await?.ok_or(AppError::NotFound("task not found"))?;
+    let event = if task.completed { "task.completed" } else { "task.reopened" };
+    webhooks::emit(&*state.db, event, &task).await;
+    // Always remove the <li> from the current list; JS will reload as needed
+    Ok(Html("").into_response())
 }
 
 #[derive(Deserialize)]
-struct UpdateBody { title: Option<String> }
+struct UpdateBody { title: Option<String>, version: Option<i64> }
 async fn update_task(
     State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
     Path(id): Path<i64>,
+    headers: HeaderMap,
     Json(body): Json<UpdateBody>,
-) -> impl IntoResponse {
-    if let Some(title) = body.title {
-        let _ = sqlx::query(r#"UPDATE tasks SET title = ?1, updated_at = datetime('now') WHERE id = ?2"#)
-            .bind(title.trim())
-            .bind(id)
-            .execute(&state.pool).await;
-        return StatusCode::NO_CONTENT.into_response();
+) -> AppResult {
+    require_task_role(&*state.db, &user, id, Role::Editor).await?;
+    let expected = expected_version(&headers, body.version)?;
+    let Some(title) = body.title else {
+        return Err(AppError::invalid("title is required"));
+    };
+    let title = validate::title("title", &title)?;
+    match state.db.rename_task(id, &title, &user.0, expected).await.context("could not rename task")? {
+        Some(version) => Ok((StatusCode::NO_CONTENT, [(header::ETAG, etag(version))]).into_response()),
+        None => version_conflict(&*state.db, id).await,
     }
-    StatusCode::BAD_REQUEST.into_response()
 }
 
-// --- PATCH: Add #[serde(rename_all = "camelCase")] to ensure JSON keys match JS ---
-#[derive(Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct ReorderBody { bucket: String, ordered_ids: Vec<i64> }
-async fn reorder_bucket(
-    State(state): State<AppState>,
-    Json(body): Json<ReorderBody>,
-) -> impl IntoResponse {
-    let _b = parse_bucket(&body.bucket).unwrap_or(Bucket::UrgentImportant);
-    let mut tx = state.pool.begin().await.unwrap();
-    for (idx, id) in body.ordered_ids.iter().enumerate() {
-        let _ = sqlx::query(r#"UPDATE tasks SET position = ?1, updated_at = datetime('now') WHERE id = ?2"#)
-            .bind((idx as i64) + 1)
-            .bind(id)
-            .execute(&mut *tx).await;
+// Version the client last saw, from `If-Match` (ETag form) or the body's `version`.
+// `Ok(None)` means `If-Match: *`: overwrite whatever is stored.
+fn expected_version(headers: &HeaderMap, body_version: Option<i64>) -> AppResult<Option<i64>> {
+    if let Some(value) = headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok()) {
+        let value = value.trim();
+        if value == "*" {
+            return Ok(None);
+        }
+        return value
+            .trim_start_matches("W/")
+            .trim_matches('"')
+            .parse()
+            .map(Some)
+            .map_err(|_| AppError::BadRequest("malformed If-Match header"));
     }
-    tx.commit().await.ok();
-    StatusCode::NO_CONTENT
+    body_version
+        .map(Some)
+        .ok_or(AppError::PreconditionRequired("If-Match header or version field required"))
+}
+
+fn etag(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+// 412 carrying the task as it is now, so the client can offer a merge
+async fn version_conflict(db: &dyn Storage, id: i64) -> AppResult {
+    let t = db.task(id).await?.ok_or(AppError::NotFound("task not found"))?;
+    Ok((StatusCode::PRECONDITION_FAILED, [(header::ETAG, etag(t.version))], Json(t)).into_response())
+}
+
+// A task for a webhook payload once its change is committed; failing to read
+// it back only costs the event, so it is logged rather than returned.
+async fn fetch_task(db: &dyn Storage, id: i64) -> Option<Task> {
+    db.task(id).await.inspect_err(|e| warn!(id, "could not load task: {e}")).ok().flatten()
 }
 
+// --- PATCH: Add #[serde(rename_all = "camelCase")] to ensure JSON keys match JS ---
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct MoveBody { id: i64, bucket: String, index: Option<usize> }
+struct MoveBody {
+    id: i64,
+    bucket: String,
+    // Destination column as the client sees it after the drop, moved task included
+    ordered_ids: Option<Vec<i64>>,
+    // Source column after the drag; only sent when it differs from the destination
+    source_ids: Option<Vec<i64>>,
+    // Neighbours to drop between when no full ordering is sent
+    prev_id: Option<i64>,
+    next_id: Option<i64>,
+    version: Option<i64>,
+}
+
+// Move a task and apply the client's view of the affected columns in one
+// transaction: either everything lands or nothing does.
 async fn move_task(
     State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    headers: HeaderMap,
     Json(body): Json<MoveBody>,
-) -> impl IntoResponse {
-    let new_bucket = parse_bucket(&body.bucket).unwrap_or(Bucket::UrgentImportant);
+) -> AppResult {
+    validate::id("id", body.id)?;
+    let new_bucket = validate::bucket("bucket", &body.bucket)?;
+    validate::ids("orderedIds", body.ordered_ids.as_deref().unwrap_or_default())?;
+    validate::ids("sourceIds", body.source_ids.as_deref().unwrap_or_default())?;
+    for (field, id) in [("prevId", body.prev_id), ("nextId", body.next_id)] {
+        if let Some(id) = id {
+            validate::id(field, id)?;
+            if id == body.id {
+                return Err(AppError::invalid(format!("{field} must not be the moved task {id}")));
+            }
+        }
+    }
+    let board_id = require_task_role(&*state.db, &user, body.id, Role::Editor).await?;
+    let expected = expected_version(&headers, body.version)?;
+
+    // Write transactions take the write lock up front so the checks below can't go stale before the writes
+    let mut tx = state.db.begin().await.context("could not start move")?;
+    if !apply_move(&mut *tx, &user, board_id, new_bucket, &body, expected).await? {
+        drop(tx);
+        return version_conflict(&*state.db, body.id).await;
+    }
+    tx.commit().await.context("could not save move")?;
+
+    // Return the updated task HTML for immediate UI update
+    Ok(match fetch_task(&*state.db, body.id).await {
+        Some(t) => {
+            webhooks::emit(&*state.db, "task.moved", &t).await;
+            views::html(&views::TaskCard { task: &t })?.into_response()
+        }
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
 
+// The body of `move_task`, run inside its transaction. Ok(false) means the
+// version check failed; any Err leaves the transaction to roll back.
+async fn apply_move(
+    conn: &mut dyn Transaction,
+    user: &CurrentUser,
+    board_id: i64,
+    new_bucket: Bucket,
+    body: &MoveBody,
+    expected: Option<i64>,
+) -> AppResult<bool> {
+    let placement = conn.placement(body.id).await?.ok_or(AppError::NotFound("task not found"))?;
+    let on_other_plan = matches!(placement.bucket, Bucket::Today) && placement.assignee.as_deref().is_some_and(|a| a != user.0);
+    // The column the task was dragged out of, as this user sees the board
+    let source = if on_other_plan {
+        placement.task_type.bucket()
+    } else {
+        placement.bucket
+    };
     let new_task_type = match new_bucket {
-        Bucket::UrgentImportant => Some(TaskType::UrgentImportant),
-        Bucket::UrgentNotImportant => Some(TaskType::UrgentNotImportant),
-        Bucket::NotUrgentImportant => Some(TaskType::NotUrgentImportant),
-        Bucket::NotUrgentNotImportant => Some(TaskType::NotUrgentNotImportant),
         Bucket::Today => None,
+        quadrant => Some(TaskType::from_bucket(quadrant)),
     };
+    // Re-classifying someone else's day-plan task leaves it on their Today list
+    let reclassify_only = on_other_plan && new_task_type.is_some();
 
-    let pos = body.index.unwrap_or(0) as i64 + 1;
+    let dest = match &body.ordered_ids {
+        Some(ids) => {
+            if ids.iter().filter(|&&id| id == body.id).count() != 1 {
+                return Err(AppError::invalid(format!("orderedIds must list task {} exactly once", body.id)));
+            }
+            Some(checked_column_order(conn, board_id, new_bucket, &user.0, ids, body.id).await?)
+        }
+        None => None,
+    };
+    let source_order = match &body.source_ids {
+        Some(ids) if source.as_str() != new_bucket.as_str() => {
+            if ids.contains(&body.id) {
+                return Err(AppError::invalid(format!("sourceIds must not list the moved task {}", body.id)));
+            }
+            Some(checked_column_order(conn, board_id, source, &user.0, ids, body.id).await?)
+        }
+        _ => None,
+    };
 
-    if let Some(tp) = new_task_type {
-        let _ = sqlx::query(
-            r#"UPDATE tasks SET bucket = ?1, task_type = ?2, position = ?3, updated_at = datetime('now') WHERE id = ?4"#,
-        )
-        .bind(new_bucket.as_str())
-        .bind(tp.as_str())
-        .bind(pos)
-        .bind(body.id)
-        .execute(&state.pool).await;
-        // Fetch and return the updated task HTML for immediate UI update
-        if let Ok(Some(r)) = sqlx::query(
-            r#"SELECT id, title, task_type, bucket, completed, position, created_at, updated_at FROM tasks WHERE id = ?1"#,
-        )
-        .bind(body.id)
-        .fetch_optional(&state.pool).await {
-            let t = Task {
-                id: r.get("id"),
-                title: r.get("title"),
-                task_type: match r.get::<String, _>("task_type").as_str() {
-                    "UrgentImportant" => TaskType::UrgentImportant,
-                    "UrgentNotImportant" => TaskType::UrgentNotImportant,
-                    "NotUrgentImportant" => TaskType::NotUrgentImportant,
-                    _ => TaskType::NotUrgentNotImportant,
-                },
-                bucket: parse_bucket(&r.get::<String, _>("bucket")).unwrap_or(Bucket::UrgentImportant),
-                completed: r.get::<i64, _>("completed") != 0,
-                position: r.get("position"),
-                created_at: Utc::now(),
-                updated_at: Utc::now(),
-            };
-            return Html(render_task(&t)).into_response();
+    let mut key = None;
+    if !reclassify_only {
+        key = Some(match &dest {
+            Some(order) => {
+                let (prev, next) = order.neighbours(body.id);
+                match order.rekey(conn, new_bucket, Some(body.id)).await? {
+                    Some(key) => key,
+                    None => drop_sort_key(conn, board_id, new_bucket, body.id, prev, next).await?,
+                }
+            }
+            None => {
+                // Same rule as orderedIds: the cards dropped between have to be shown in the column
+                let shown = conn.column(board_id, new_bucket, &user.0).await?;
+                for id in [body.prev_id, body.next_id].into_iter().flatten() {
+                    if !shown.iter().any(|(row_id, _, _)| *row_id == id) {
+                        return Err(AppError::Conflict(format!(
+                            "task {id} is no longer in {} on this board; reload and try again",
+                            new_bucket.as_str()
+                        )));
+                    }
+                }
+                drop_sort_key(conn, board_id, new_bucket, body.id, body.prev_id, body.next_id).await?
+            }
+        });
+    } else if let Some(order) = &dest {
+        order.rekey(conn, new_bucket, None).await?;
+    }
+    if let Some(order) = &source_order {
+        order.rekey(conn, source, None).await?;
+    }
+
+    let res = match (new_task_type, key) {
+        (Some(tp), None) => conn.reclassify(body.id, tp, &user.0, expected).await,
+        (Some(_), Some(key)) => conn.move_to_quadrant(body.id, new_bucket, &key, &user.0, expected).await,
+        // Today is per-user: dropping a task there puts it on the caller's day plan
+        (None, key) => conn.move_to_today(body.id, &key.unwrap_or_default(), &user.0, expected).await,
+    };
+    Ok(res.context("move failed")?)
+}
+
+// A column ordering sent by the client, checked against the board. `own`
+// holds the listed tasks that really sit in the column's bucket, in the
+// client's order; `current` is the order the database has for them, and
+// `collided` says two of them share a sort key, so that order is arbitrary.
+struct ColumnOrder {
+    listed: Vec<i64>,
+    own: Vec<i64>,
+    current: Vec<i64>,
+    collided: bool,
+}
+
+impl ColumnOrder {
+    // Nearest tasks of the column's own bucket above and below `id`
+    fn neighbours(&self, id: i64) -> (Option<i64>, Option<i64>) {
+        let at = self.listed.iter().position(|&x| x == id).unwrap_or(0);
+        let is_own = |x: &&i64| self.own.contains(x);
+        let prev = self.listed[..at].iter().rev().find(is_own).copied();
+        let next = self.listed[at + 1..].iter().find(is_own).copied();
+        (prev, next)
+    }
+
+    // When the client's order of the existing tasks differs from the stored
+    // one, or their keys collide so nothing fits between them, give the listed
+    // tasks fresh keys in the client's order and return the key for `moved`.
+    // None if nothing needed changing.
+    async fn rekey(
+        &self,
+        conn: &mut dyn Transaction,
+        bucket: Bucket,
+        moved: Option<i64>,
+    ) -> AppResult<Option<String>> {
+        let others: Vec<i64> = self.own.iter().copied().filter(|&id| Some(id) != moved).collect();
+        if others == self.current && !self.collided {
+            return Ok(None);
         }
-    } else {
-        let _ = sqlx::query(
-            r#"UPDATE tasks SET bucket = ?1, position = ?2, updated_at = datetime('now') WHERE id = ?3"#,
-        )
-        .bind(new_bucket.as_str())
-        .bind(pos)
-        .bind(body.id)
-        .execute(&state.pool).await;
-        if let Ok(Some(r)) = sqlx::query(
-            r#"SELECT id, title, task_type, bucket, completed, position, created_at, updated_at FROM tasks WHERE id = ?1"#,
-        )
-        .bind(body.id)
-        .fetch_optional(&state.pool).await {
-            let t = Task {
-                id: r.get("id"),
-                title: r.get("title"),
-                task_type: match r.get::<String, _>("task_type").as_str() {
-                    "UrgentImportant" => TaskType::UrgentImportant,
-                    "UrgentNotImportant" => TaskType::UrgentNotImportant,
-                    "NotUrgentImportant" => TaskType::NotUrgentImportant,
-                    _ => TaskType::NotUrgentNotImportant,
-                },
-                bucket: parse_bucket(&r.get::<String, _>("bucket")).unwrap_or(Bucket::UrgentImportant),
-                completed: r.get::<i64, _>("completed") != 0,
-                position: r.get("position"),
-                created_at: Utc::now(),
-                updated_at: Utc::now(),
+        let order: Vec<i64> = self
+            .listed
+            .iter()
+            .copied()
+            .filter(|&id| Some(id) == moved || self.own.contains(&id))
+            .collect();
+        let mut moved_key = None;
+        for (id, key) in order.iter().zip(sort_key::spread(order.len())) {
+            if Some(*id) == moved {
+                moved_key = Some(key);
+                continue;
+            }
+            conn.set_sort_key(*id, bucket, &key).await?;
+        }
+        Ok(moved_key)
+    }
+}
+
+// Check that every id the client listed for a column (other than the moved
+// task) is an open task shown in that column on this board. Tasks the client
+// hasn't seen yet are allowed to be missing; they keep their place.
+async fn checked_column_order(
+    conn: &mut dyn Transaction,
+    board_id: i64,
+    column: Bucket,
+    viewer: &str,
+    ids: &[i64],
+    moved: i64,
+) -> AppResult<ColumnOrder> {
+    // Same rule as fetch_all_grouped: quadrants also show others' day-plan tasks of their type
+    let rows = conn.column(board_id, column, viewer).await?;
+
+    let mut seen = std::collections::HashSet::new();
+    let mut own = Vec::new();
+    for &id in ids {
+        if !seen.insert(id) {
+            return Err(AppError::invalid(format!("task {id} is listed twice for {}", column.as_str())));
+        }
+        if id == moved {
+            continue;
+        }
+        match rows.iter().find(|(row_id, _, _)| *row_id == id) {
+            Some((_, true, _)) => own.push(id),
+            Some((_, false, _)) => {}
+            None => {
+                return Err(AppError::Conflict(format!(
+                    "task {id} is no longer in {} on this board; reload and try again",
+                    column.as_str()
+                )))
+            }
+        }
+    }
+    let listed: Vec<&(i64, bool, String)> = rows.iter().filter(|(id, is_own, _)| *is_own && *id != moved && own.contains(id)).collect();
+    let collided = listed.windows(2).any(|w| w[0].2 == w[1].2);
+    let current = listed.iter().map(|(id, _, _)| *id).collect();
+    Ok(ColumnOrder { listed: ids.to_vec(), own, current, collided })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BulkBody {
+    action: String, // move | complete | restore | delete | tag
+    ids: Vec<i64>,
+    bucket: Option<String>, // destination for "move"
+    #[serde(default)]
+    add_tags: Vec<String>,
+    #[serde(default)]
+    remove_tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct BulkResult {
+    id: i64,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+enum BulkAction {
+    Move(Bucket),
+    Complete,
+    Restore,
+    Delete,
+    Tag { add: Vec<String>, remove: Vec<String> },
+}
+
+// Apply one action to many tasks in a single transaction. Tasks the caller
+// can't edit, or that are already in the requested state, are reported and
+// skipped; the rest are applied together.
+async fn bulk_tasks(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Json(body): Json<BulkBody>,
+) -> AppResult {
+    validate::ids("ids", &body.ids)?;
+    let action = match body.action.as_str() {
+        "move" => BulkAction::Move(validate::bucket("bucket", body.bucket.as_deref().unwrap_or_default())?),
+        "complete" => BulkAction::Complete,
+        "restore" => BulkAction::Restore,
+        "delete" => BulkAction::Delete,
+        "tag" => {
+            let add: Vec<String> = body.add_tags.iter().filter_map(|t| normalize_tag(t)).collect();
+            let remove: Vec<String> = body.remove_tags.iter().filter_map(|t| normalize_tag(t)).collect();
+            if add.is_empty() && remove.is_empty() {
+                return Err(AppError::invalid("addTags and removeTags hold no tags; tag needs at least one"));
+            }
+            BulkAction::Tag { add, remove }
+        }
+        other => return Err(AppError::invalid(format!("action must be move, complete, restore, delete or tag, not {other:?}"))),
+    };
+
+    let mut results = Vec::new();
+    let mut allowed = Vec::new();
+    for &id in &body.ids {
+        match require_task_role(&*state.db, &user, id, Role::Editor).await {
+            Ok(_) => {
+                allowed.push(results.len());
+                results.push(BulkResult { id, ok: true, error: None });
+            }
+            Err(AppError::Forbidden(_)) => results.push(BulkResult { id, ok: false, error: Some("forbidden".to_string()) }),
+            Err(AppError::NotFound(_)) => results.push(BulkResult { id, ok: false, error: Some("not found".to_string()) }),
+            Err(e) => return Err(e),
+        }
+    }
+
+    // Deleted tasks can't be read back afterwards for their webhook payloads
+    let mut deleted = Vec::new();
+    if let BulkAction::Delete = action {
+        for &i in &allowed {
+            deleted.extend(state.db.task(results[i].id).await?);
+        }
+    }
+
+    let mut tx = state.db.begin().await.context("bulk update failed")?;
+    for &i in &allowed {
+        if let Err(error) = apply_bulk(&mut *tx, &user, &action, results[i].id).await.context("bulk update failed")? {
+            results[i].ok = false;
+            results[i].error = Some(error.to_string());
+        }
+    }
+    tx.commit().await.context("bulk update failed")?;
+
+    let event = match action {
+        BulkAction::Move(_) => Some("task.moved"),
+        BulkAction::Complete => Some("task.completed"),
+        BulkAction::Restore => Some("task.reopened"),
+        BulkAction::Delete => Some("task.deleted"),
+        BulkAction::Tag { .. } => None,
+    };
+    if let Some(event) = event {
+        for r in results.iter().filter(|r| r.ok) {
+            let task = match action {
+                BulkAction::Delete => deleted.iter().find(|t| t.id == r.id).cloned(),
+                _ => fetch_task(&*state.db, r.id).await,
             };
-            return Html(render_task(&t)).into_response();
+            if let Some(task) = task {
+                webhooks::emit(&*state.db, event, &task).await;
+            }
         }
     }
-    StatusCode::NO_CONTENT.into_response()
+    Ok(Json(serde_json::json!({ "results": results })).into_response())
 }
 
-// Render completed tasks list for the panel
-async fn completed_tasks(State(state): State<AppState>) -> impl IntoResponse {
-    let rows = sqlx::query(
-        r#"SELECT id, title, task_type, bucket, completed, position, created_at, updated_at FROM tasks WHERE completed = 1 ORDER BY updated_at DESC LIMIT 100"#
-    )
-    .fetch_all(&state.pool)
-    .await
-    .unwrap_or_default();
-    use sqlx::Row;
-    let mut html = String::new();
-    html.push_str("<div class='completed-tasklist-header'><span>Task</span><span>Completed</span><span></span><span></span></div>");
-    html.push_str("<ul class='completed-tasklist'>");
-        for r in rows {
-            let id: i64 = r.get("id");
-            let title: String = r.get("title");
-            let updated_at: String = r.get::<String, _>("updated_at");
-            // Format the completed time for display: date and time on separate lines for mobile
-            let updated_at_fmt = updated_at.replace('T', " ");
-            let completed_time = updated_at_fmt.split('.').next().unwrap_or(updated_at_fmt.as_str());
-            let (date_part, time_part) = if let Some((d, t)) = completed_time.split_once(' ') {
-                (d, t)
+// One task's share of a bulk action. The outer error aborts the whole batch;
+// the inner one only skips this task.
+async fn apply_bulk(
+    conn: &mut dyn Transaction,
+    user: &CurrentUser,
+    action: &BulkAction,
+    id: i64,
+) -> Result<Result<(), &'static str>, sqlx::Error> {
+    let Some(task) = conn.placement(id).await? else {
+        return Ok(Err("not found"));
+    };
+    match action {
+        BulkAction::Move(new_bucket) => {
+            if task.completed {
+                return Ok(Err("completed tasks can't be moved"));
+            }
+            let on_other_plan = matches!(task.bucket, Bucket::Today) && task.assignee.is_some_and(|a| a != user.0);
+            if let (true, Bucket::Today) = (on_other_plan, new_bucket) {
+                return Ok(Err("already on someone else's Today list"));
+            }
+            if on_other_plan {
+                // Same rule as a drag: re-classify without taking it off their day plan
+                conn.reclassify(id, TaskType::from_bucket(*new_bucket), &user.0, None).await?;
+                return Ok(Ok(()));
+            }
+            let key = append_sort_key(conn, task.board_id, *new_bucket).await?;
+            if let Bucket::Today = new_bucket {
+                conn.move_to_today(id, &key, &user.0, None).await?;
             } else {
-                (completed_time, "")
-            };
-            html.push_str(&format!(
-                "<li class='completed-task' data-id='{}'>\
-                        <span class='completed-title'>{}</span>\
-                        <span class='completed-time'><span class='completed-date'>{}</span><span class='completed-time-only'>{}</span></span>\
-                        <span class='button-group'>\
-                            <button class='undo-btn' hx-post='/tasks/{}/toggle' hx-target='closest li.completed-task' hx-swap='outerHTML' hx-on::afterSwap='document.dispatchEvent(new CustomEvent(\"completed-task-undone\"))' hx-on::afterRequest='window.location.reload()' title='Undo'><span class='svg-undo'></span></button>\
-                            <button class='delete-btn' hx-post='/tasks/{}/delete' hx-target='closest li.completed-task' hx-swap='outerHTML' title='Delete'><span class='svg-x'></span></button>\
-                        </span>\
-                </li>",
-                id, title, date_part, time_part, id, id
-            ));
-    }
-    html.push_str("</ul>");
-    Html(html)
-}
-
-async fn basic_auth(req: Request<axum::body::Body>, next: Next) -> Result<Response, StatusCode> {
-    let env_user = std::env::var("EISENHOWER_USERNAME").unwrap_or_else(|_| "admin".to_string());
-    let env_pass = std::env::var("EISENHOWER_PASSWORD").unwrap_or_else(|_| "password".to_string());
-    if let Some(auth_header) = req.headers().get(header::AUTHORIZATION) {
-        if let Ok(auth_str) = auth_header.to_str() {
-            if let Some(basic) = auth_str.strip_prefix("Basic ") {
-                if let Ok(decoded) = STANDARD.decode(basic) {
-                    if let Ok(decoded_str) = std::str::from_utf8(&decoded) {
-                        let mut parts = decoded_str.splitn(2, ':');
-                        let username = parts.next().unwrap_or("");
-                        let password = parts.next().unwrap_or("");
-                        if username == env_user && password == env_pass {
-                            return Ok(next.run(req).await);
-                        }
-                    }
-                }
+                conn.move_to_quadrant(id, *new_bucket, &key, &user.0, None).await?;
+            }
+        }
+        BulkAction::Complete | BulkAction::Restore => {
+            let done = matches!(action, BulkAction::Complete);
+            if task.completed == done {
+                return Ok(Err(if done { "already completed" } else { "not completed" }));
+            }
+            conn.set_completed(id, done, &user.0).await?;
+        }
+        BulkAction::Delete => {
+            conn.delete_task(id).await?;
+        }
+        BulkAction::Tag { add, remove } => {
+            for tag in add {
+                conn.add_tag(id, tag).await?;
             }
+            for tag in remove {
+                conn.remove_tag(id, tag).await?;
+            }
+            conn.touch_task(id, &user.0).await?;
         }
     }
-    let mut res = Response::new("Unauthorized".into());
-    *res.status_mut() = StatusCode::UNAUTHORIZED;
-    res.headers_mut().insert(header::WWW_AUTHENTICATE, "Basic realm=\"User Visible Realm\"".parse().unwrap());
-    Ok(res)
-}
\ No newline at end of file
+    Ok(Ok(()))
+}
+
+// "#Work " -> "work"; tags are single words of letters, digits, '-' and '_'
+fn normalize_tag(s: &str) -> Option<String> {
+    let tag: String = s
+        .trim()
+        .trim_start_matches('#')
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .take(32)
+        .collect::<String>()
+        .to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
+
+// Key placing a new or moved task after everything else in the bucket
+async fn append_sort_key(conn: &mut dyn Transaction, board_id: i64, bucket: Bucket) -> sqlx::Result<String> {
+    let last = conn.last_sort_key(board_id, bucket).await?;
+    Ok(sort_key::between(last.as_deref(), None))
+}
+
+// Key for dropping a task between the cards the client saw above (`prev_id`)
+// and below (`next_id`) it. Only one neighbour is trusted and the other bound
+// comes from the database, so a stale or concurrent view of the column still
+// yields a key inside the bucket's current order.
+async fn drop_sort_key(
+    conn: &mut dyn Transaction,
+    board_id: i64,
+    bucket: Bucket,
+    moved_id: i64,
+    prev_id: Option<i64>,
+    next_id: Option<i64>,
+) -> sqlx::Result<String> {
+    async fn neighbour_key(
+        conn: &mut dyn Transaction,
+        board_id: i64,
+        bucket: Bucket,
+        moved_id: i64,
+        id: Option<i64>,
+    ) -> sqlx::Result<Option<String>> {
+        match id {
+            Some(id) => conn.sort_key_of(id, board_id, bucket, moved_id).await,
+            None => Ok(None),
+        }
+    }
+
+    if let Some(lo) = neighbour_key(conn, board_id, bucket, moved_id, prev_id).await? {
+        let hi = conn.sort_key_after(board_id, bucket, moved_id, &lo).await?;
+        return Ok(sort_key::between(Some(&lo), hi.as_deref()));
+    }
+    if let Some(hi) = neighbour_key(conn, board_id, bucket, moved_id, next_id).await? {
+        let lo = conn.sort_key_before(board_id, bucket, moved_id, &hi).await?;
+        return Ok(sort_key::between(lo.as_deref(), Some(&hi)));
+    }
+    append_sort_key(conn, board_id, bucket).await
+}
+
+// Rewrite the keys of any bucket whose keys have grown long or collided,
+// keeping the current order. Doesn't bump task versions.
+async fn rebalance_sort_keys(db: &dyn Storage) -> anyhow::Result<()> {
+    for (board_id, bucket) in db.unbalanced_buckets(sort_key::REBALANCE_KEY_LEN).await? {
+        let mut tx = db.begin().await?;
+        let ids = tx.bucket_ids(board_id, &bucket).await?;
+        for (id, key) in ids.iter().zip(sort_key::spread(ids.len())) {
+            tx.rewrite_sort_key(*id, &key).await?;
+        }
+        tx.commit().await?;
+        info!(?board_id, %bucket, tasks = ids.len(), "rebalanced sort keys");
+    }
+    Ok(())
+}
+
+// The caller's email-in addresses, creating their secret on first use
+async fn render_inbox(state: &AppState, username: &str) -> AppResult<Html<String>> {
+    let Some(domain) = &state.mail_domain else {
+        return views::html(&views::Inbox { domain: None, secret: String::new() });
+    };
+    let secret = state.db.inbox_secret(username, &smtp::new_secret()).await?;
+    views::html(&views::Inbox { domain: Some(domain), secret })
+}
+
+async fn inbox(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+) -> AppResult<Html<String>> {
+    render_inbox(&state, &user.0).await
+}
+
+async fn rotate_inbox(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+) -> AppResult<Html<String>> {
+    state.db.delete_inbox(&user.0).await?;
+    render_inbox(&state, &user.0).await
+}
+
+// Subscriptions with their delivery log, then incoming tokens, each when its feature is on
+async fn render_webhooks(db: &dyn Storage, board_id: i64, new_token: Option<&str>) -> AppResult<Html<String>> {
+    let features = &config::get().features;
+    let (hooks, deliveries) = if features.webhooks {
+        (Some(db.webhooks(board_id).await?), db.recent_deliveries(board_id, 25).await?)
+    } else {
+        (None, Vec::new())
+    };
+    let tokens = if features.incoming { Some(db.incoming_tokens(board_id).await?) } else { None };
+    views::html(&views::Webhooks {
+        board_id,
+        hooks,
+        events: webhooks::EVENTS.join(", "),
+        deliveries,
+        tokens,
+        new_token,
+    })
+}
+
+async fn board_webhooks(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(board_id): Path<i64>,
+) -> AppResult<Html<String>> {
+    require_board_role(&*state.db, &user, board_id, Role::Owner).await?;
+    render_webhooks(&*state.db, board_id, None).await
+}
+
+#[derive(Deserialize)]
+struct WebhookForm { url: String, events: String, task_type: String, secret: String }
+
+async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(board_id): Path<i64>,
+    Form(form): Form<WebhookForm>,
+) -> AppResult<Html<String>> {
+    require_board_role(&*state.db, &user, board_id, Role::Owner).await?;
+    let url = form.url.trim();
+    webhooks::Targets::configured().check(url).await.map_err(AppError::invalid)?;
+    let events: Vec<&str> = form.events.split(',').map(str::trim).filter(|e| !e.is_empty()).collect();
+    if let Some(bad) = events.iter().find(|e| !webhooks::EVENTS.contains(e)) {
+        return Err(AppError::invalid(format!("unknown event {bad}")));
+    }
+    let events = if events.is_empty() { "*".to_string() } else { events.join(",") };
+    let task_type = parse_task_type(&form.task_type);
+    let secret = match form.secret.trim() {
+        "" => hex::encode(rand::random::<[u8; 16]>()),
+        s => s.to_string(),
+    };
+    state.db.create_webhook(board_id, url, &events, task_type, &secret, &user.0).await.context("could not add webhook")?;
+    render_webhooks(&*state.db, board_id, None).await
+}
+
+async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path((board_id, hook_id)): Path<(i64, i64)>,
+) -> AppResult<Html<String>> {
+    require_board_role(&*state.db, &user, board_id, Role::Owner).await?;
+    state.db.delete_webhook(board_id, hook_id).await?;
+    render_webhooks(&*state.db, board_id, None).await
+}
+
+#[derive(Deserialize)]
+struct TokenForm { label: String }
+
+async fn create_incoming_token(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(board_id): Path<i64>,
+    Form(form): Form<TokenForm>,
+) -> AppResult<Html<String>> {
+    require_board_role(&*state.db, &user, board_id, Role::Owner).await?;
+    let label = validate::line("label", &form.label, validate::MAX_NAME_CHARS)?;
+    let token = hex::encode(rand::random::<[u8; 24]>());
+    state
+        .db
+        .create_incoming_token(board_id, &label, &hex::encode(Sha256::digest(token.as_bytes())), &user.0)
+        .await
+        .context("could not create token")?;
+    render_webhooks(&*state.db, board_id, Some(&token)).await
+}
+
+async fn delete_incoming_token(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path((board_id, token_id)): Path<(i64, i64)>,
+) -> AppResult<Html<String>> {
+    require_board_role(&*state.db, &user, board_id, Role::Owner).await?;
+    state.db.delete_incoming_token(board_id, token_id).await?;
+    render_webhooks(&*state.db, board_id, None).await
+}
+
+#[derive(Deserialize)]
+struct IncomingTask {
+    title: String,
+    quadrant: Option<String>, // "ui", "uni", "nui", "nun", "today" or a Bucket name
+    notes: Option<String>,
+    external_id: Option<String>,
+}
+
+// Token-authenticated task creation for CI, monitoring and other tools.
+// With an `external_id` already on the board, the existing task is updated
+// (and reopened if it was completed) instead of adding a copy.
+async fn incoming_task(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<IncomingTask>,
+) -> AppResult {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let token_hash = hex::encode(Sha256::digest(token.trim().as_bytes()));
+    let row = state.db.incoming_token(&token_hash).await?;
+    // A token stops working once its creator can no longer edit the board
+    let (token_id, board_id, username) = match row {
+        Some((id, board_id, username)) if state.db.board_role(&username, board_id).await? >= Some(Role::Editor) => (id, board_id, username),
+        _ => return Ok((StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], "invalid token").into_response()),
+    };
+    state.db.touch_incoming_token(token_id).await?;
+
+    let title = validate::title("title", &body.title)?;
+    let bucket = match body.quadrant.as_deref().map(str::trim) {
+        None | Some("") => Bucket::UrgentImportant,
+        Some(q) => validate::quadrant("quadrant", q)?,
+    };
+    let notes = validate::notes("notes", body.notes.as_deref())?;
+    let external_id = match body.external_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(e) => Some(validate::line("external_id", e, validate::MAX_NAME_CHARS)?),
+    };
+
+    if let Some(ext) = &external_id {
+        let updated = update_external_task(&*state.db, board_id, ext, &title, notes.as_deref(), &username).await;
+        if let Some(id) = updated.context("could not update task")? {
+            return Ok((StatusCode::OK, Json(serde_json::json!({ "id": id, "created": false }))).into_response());
+        }
+    }
+    let mut draft = TaskDraft::new(title.clone(), bucket);
+    draft.notes = notes.clone();
+    draft.external_id = external_id.clone();
+    match create_task(&*state.db, board_id, &username, draft).await {
+        Ok(task) => Ok((StatusCode::CREATED, Json(serde_json::json!({ "id": task.id, "created": true }))).into_response()),
+        Err(e) => {
+            // Lost a race with an identical delivery: the unique index kept the first one
+            if let Some(ext) = &external_id {
+                if let Ok(Some(id)) = update_external_task(&*state.db, board_id, ext, &title, notes.as_deref(), &username).await {
+                    return Ok((StatusCode::OK, Json(serde_json::json!({ "id": id, "created": false }))).into_response());
+                }
+            }
+            Err(AppError::Internal(e.context("could not add task")))
+        }
+    }
+}
+
+// Refresh the task already carrying `external_id`; None when there isn't one
+async fn update_external_task(
+    db: &dyn Storage,
+    board_id: i64,
+    external_id: &str,
+    title: &str,
+    notes: Option<&str>,
+    username: &str,
+) -> anyhow::Result<Option<i64>> {
+    let Some((id, was_completed)) = db.external_task(board_id, external_id).await? else {
+        return Ok(None);
+    };
+    db.refresh_external_task(id, title, notes, username).await?;
+    if was_completed {
+        if let Some(task) = fetch_task(db, id).await {
+            webhooks::emit(db, "task.reopened", &task).await;
+        }
+    }
+    Ok(Some(id))
+}
+
+// --- JSON API (used by eisenpower-cli) ---
+
+async fn api_boards(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+) -> AppResult<Json<Vec<Board>>> {
+    Ok(Json(user_boards(&*state.db, &user.0).await.context("could not list boards")?))
+}
+
+#[derive(Deserialize)]
+struct ApiTasksQuery { board: Option<i64>, q: Option<String> }
+
+// A board's open tasks by column, as the caller sees them on the page.
+// `q` keeps tasks whose title, tags or notes contain it.
+async fn api_tasks(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Query(q): Query<ApiTasksQuery>,
+) -> AppResult {
+    let boards = user_boards(&*state.db, &user.0).await.context("could not list boards")?;
+    let current = pick_board(&boards, q.board)?;
+    let mut groups = fetch_all_grouped(&*state.db, current.id, &user.0).await.context("could not load tasks")?;
+    let needle = q.q.unwrap_or_default().to_lowercase();
+    for tasks in groups.values_mut() {
+        tasks.retain(|t| {
+            !t.completed
+                && (needle.is_empty()
+                    || t.title.to_lowercase().contains(&needle)
+                    || t.tags.iter().any(|tag| tag.contains(&needle))
+                    || t.notes.as_deref().is_some_and(|n| n.to_lowercase().contains(&needle)))
+        });
+    }
+    Ok(Json(serde_json::json!({ "board": current, "columns": groups })).into_response())
+}
+
+#[derive(Deserialize)]
+struct ApiNewTask {
+    title: String,
+    board: Option<i64>,
+    bucket: Option<String>, // without it, quick-add markers in the title decide
+}
+
+async fn api_add_task(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Json(body): Json<ApiNewTask>,
+) -> AppResult {
+    let boards = user_boards(&*state.db, &user.0).await.context("could not list boards")?;
+    let board = pick_board(&boards, body.board)?;
+    if board.role < Role::Editor {
+        return Err(AppError::Forbidden("insufficient permissions on this board"));
+    }
+    let mut draft = match body.bucket.as_deref() {
+        Some(b) => TaskDraft::new(body.title.clone(), validate::quadrant("bucket", b)?),
+        None => TaskDraft::from_quick_add(quick_add::parse(&body.title, config::today())),
+    };
+    draft.title = validate::title("title", &draft.title)?;
+    let task = create_task(&*state.db, board.id, &user.0, draft).await.context("could not add task")?;
+    Ok((StatusCode::CREATED, [(header::ETAG, etag(task.version))], Json(task)).into_response())
+}
+
+// Small select listing the user's other boards, swapped in place of the card's board button
+async fn task_board_picker(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+) -> AppResult<Html<String>> {
+    let current = require_task_role(&*state.db, &user, id, Role::Editor).await?;
+    let boards = user_boards(&*state.db, &user.0).await.context("could not list boards")?;
+    let boards = boards.into_iter().filter(|b| b.id != current && b.role >= Role::Editor).collect();
+    views::html(&views::BoardPicker { task_id: id, boards })
+}
+
+#[derive(Deserialize)]
+struct BoardTarget { board_id: i64 }
+
+// Move a task to another board, keeping its bucket and TaskType
+async fn move_task_to_board(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+    Form(form): Form<BoardTarget>,
+) -> AppResult<Html<String>> {
+    require_task_role(&*state.db, &user, id, Role::Editor).await?;
+    require_board_role(&*state.db, &user, form.board_id, Role::Editor).await?;
+    let people = state.db.board_usernames(form.board_id).await?;
+    let mut tx = state.db.begin().await?;
+    let placement = tx.placement(id).await?.ok_or(AppError::NotFound("task not found"))?;
+    // An assignee who can't see the new board is dropped, and a Today task
+    // they had planned goes back to its quadrant
+    let mut bucket = placement.bucket;
+    let mut assignee = placement.assignee;
+    if assignee.as_ref().is_some_and(|a| !people.contains(a)) {
+        assignee = None;
+        if matches!(bucket, Bucket::Today) {
+            bucket = placement.task_type.bucket();
+        }
+    }
+    let key = append_sort_key(&mut *tx, form.board_id, bucket).await?;
+    if !tx.move_to_board(id, form.board_id, bucket, assignee.as_deref(), &key, &user.0).await? {
+        return Err(AppError::NotFound("task not found"));
+    }
+    tx.commit().await.context("could not move task")?;
+    Ok(Html(String::new()))
+}
+
+// Select of the board's people, swapped in place of the card's assignee button
+async fn task_assignee_picker(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+) -> AppResult<Html<String>> {
+    let board_id = require_task_role(&*state.db, &user, id, Role::Editor).await?;
+    let current = state.db.task(id).await?.and_then(|t| t.assignee);
+    // Everyone who can see the board: its owner followed by the members
+    let names = state.db.board_usernames(board_id).await?;
+    views::html(&views::AssigneePicker { task_id: id, current, names })
+}
+
+#[derive(Deserialize)]
+struct AssignForm { assignee: String }
+
+// Assign (or unassign with an empty value) a task; a Today task follows its assignee's day plan
+async fn assign_task(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+    Form(form): Form<AssignForm>,
+) -> AppResult<Html<String>> {
+    let board_id = require_task_role(&*state.db, &user, id, Role::Editor).await?;
+    let assignee = Some(form.assignee.trim()).filter(|a| !a.is_empty());
+    if let Some(a) = assignee {
+        if !state.db.board_usernames(board_id).await?.iter().any(|u| u == a) {
+            return Err(AppError::invalid("assignee must have access to the board"));
+        }
+    }
+    state.db.assign_task(id, assignee, &user.0).await.context("could not assign task")?;
+    Ok(Html(String::new()))
+}
+
+#[derive(Deserialize)]
+struct NewBoard { name: String }
+
+async fn create_board(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Form(form): Form<NewBoard>,
+) -> AppResult<Redirect> {
+    let name = validate::line("name", &form.name, validate::MAX_NAME_CHARS)?;
+    let mut tx = state.db.begin().await?;
+    let id = tx.create_board(&name, &user.0).await?;
+    tx.commit().await.context("could not create board")?;
+    Ok(Redirect::to(&format!("/?board={}", id)))
+}
+
+async fn delete_board(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+) -> AppResult<Redirect> {
+    require_board_role(&*state.db, &user, id, Role::Owner).await?;
+    let boards = user_boards(&*state.db, &user.0).await.context("could not list boards")?;
+    if boards.iter().filter(|b| b.role == Role::Owner).count() <= 1 {
+        return Err(AppError::invalid("cannot delete the last board"));
+    }
+    let mut tx = state.db.begin().await?;
+    tx.delete_board(id).await?;
+    tx.commit().await.context("could not delete board")?;
+    Ok(Redirect::to("/"))
+}
+
+async fn user_exists(db: &dyn Storage, username: &str) -> sqlx::Result<bool> {
+    Ok(username == admin_username() || db.user_exists(username).await?)
+}
+
+// Member list and share form for the owner's Share panel
+async fn render_members(db: &dyn Storage, board_id: i64) -> AppResult<Html<String>> {
+    views::html(&views::Members { board_id, members: db.members(board_id).await? })
+}
+
+async fn board_members(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+) -> AppResult<Html<String>> {
+    require_board_role(&*state.db, &user, id, Role::Owner).await?;
+    render_members(&*state.db, id).await
+}
+
+#[derive(Deserialize)]
+struct ShareForm { username: String, role: String }
+
+// Add a member or change an existing member's role
+async fn share_board(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+    Form(form): Form<ShareForm>,
+) -> AppResult<Html<String>> {
+    require_board_role(&*state.db, &user, id, Role::Owner).await?;
+    let username = form.username.trim();
+    let role = match parse_role(&form.role) {
+        Some(r @ (Role::Viewer | Role::Editor)) => r,
+        _ => return Err(AppError::invalid("role must be viewer or editor")),
+    };
+    if username == user.0 {
+        return Err(AppError::invalid("you already own this board"));
+    }
+    if !user_exists(&*state.db, username).await? {
+        return Err(AppError::NotFound("no such user"));
+    }
+    state.db.set_member(id, username, role).await.context("could not share board")?;
+    render_members(&*state.db, id).await
+}
+
+async fn unshare_board(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Path((id, username)): Path<(i64, String)>,
+) -> AppResult<Html<String>> {
+    require_board_role(&*state.db, &user, id, Role::Owner).await?;
+    state.db.remove_member(id, &username).await.context("could not remove member")?;
+    render_members(&*state.db, id).await
+}
+
+async fn render_users(db: &dyn Storage) -> AppResult<Html<String>> {
+    views::html(&views::Users { admin: admin_username(), users: db.users().await? })
+}
+
+async fn list_users(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+) -> AppResult<Html<String>> {
+    if !user.is_admin() {
+        return Err(AppError::Forbidden("admin only"));
+    }
+    render_users(&*state.db).await
+}
+
+#[derive(Deserialize)]
+struct NewUser { username: String, password: String }
+
+async fn create_user(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Form(form): Form<NewUser>,
+) -> AppResult<Html<String>> {
+    if !user.is_admin() {
+        return Err(AppError::Forbidden("admin only"));
+    }
+    let username = validate::line("username", &form.username, validate::MAX_NAME_CHARS)?;
+    if username.contains(':') {
+        // Basic auth splits on the first ':'
+        return Err(AppError::invalid("username must not contain ':'"));
+    }
+    if form.password.is_empty() {
+        return Err(AppError::invalid("password is required"));
+    }
+    if user_exists(&*state.db, &username).await? {
+        return Err(AppError::Conflict("user already exists".to_string()));
+    }
+    state.db.create_user(&username, &password::hash(&form.password)).await.context("could not create user")?;
+    render_users(&*state.db).await
+}
+
+// Render completed tasks list for the panel
+async fn completed_tasks(
+    State(state): State<AppState>,
+    Extension(user): Extension<CurrentUser>,
+    Query(q): Query<BoardQuery>,
+) -> AppResult<Html<String>> {
+    let board_id = q.board.unwrap_or_default();
+    require_board_role(&*state.db, &user, board_id, Role::Viewer).await?;
+    let tasks = state.db.completed_tasks(board_id, 100).await.context("could not load completed tasks")?;
+    views::html(&views::Completed { tasks })
+}
+
+// For monitoring: 503 when scheduled backups are on but the newest one is overdue
+async fn backup_status(Extension(user): Extension<CurrentUser>) -> AppResult {
+    if !user.is_admin() {
+        return Err(AppError::Forbidden("admin only"));
+    }
+    let (healthy, body) = backups::status();
+    let code = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    Ok((code, Json(body)).into_response())
+}
+
+fn admin_username() -> String {
+    config::get().auth.admin_username.clone()
+}
+
+async fn basic_auth(
+    State(state): State<AppState>,
+    mut req: Request<axum::body::Body>,
+    next: Next,
+) -> AppResult {
+    let auth = &config::get().auth;
+    if auth.mode == config::AuthMode::None {
+        req.extensions_mut().insert(CurrentUser(auth.admin_username.clone()));
+        return Ok(next.run(req).await);
+    }
+    let env_user = &auth.admin_username;
+    let env_pass = &auth.admin_password;
+    let ip = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip());
+    if let Some(auth_header) = req.headers().get(header::AUTHORIZATION) {
+        if let Ok(auth_str) = auth_header.to_str() {
+            if let Some(basic) = auth_str.strip_prefix("Basic ") {
+                if let Ok(decoded) = STANDARD.decode(basic) {
+                    if let Ok(decoded_str) = std::str::from_utf8(&decoded) {
+                        let mut parts = decoded_str.splitn(2, ':');
+                        let username = parts.next().unwrap_or("");
+                        let password = parts.next().unwrap_or("");
+                        if let Some(wait) = state.logins.blocked(ip, username) {
+                            return Ok(too_many_attempts(wait));
+                        }
+                        let authenticated = if username == env_user {
+                            password::matches(password, env_pass)
+                        } else if state.verified.contains(username, password) {
+                            true
+                        } else {
+                            let ok = match state.db.password_hash(username).await? {
+                                Some(stored) => password::verify_blocking(password, stored).await,
+                                None => false,
+                            };
+                            if ok {
+                                state.verified.insert(username, password);
+                            }
+                            ok
+                        };
+                        if !authenticated {
+                            state.logins.failed(ip, username);
+                        } else {
+                            state.logins.succeeded(ip, username);
+                            let user = CurrentUser(username.to_string());
+                            req.extensions_mut().insert(user);
+                            return Ok(next.run(req).await);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let mut res = Response::new("Unauthorized".into());
+    *res.status_mut() = StatusCode::UNAUTHORIZED;
+    res.headers_mut().insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Basic realm=\"User Visible Realm\""));
+    Ok(res)
+}
+
+// 429 for a client or username held back after failed logins; the password isn't even checked
+fn too_many_attempts(wait: Duration) -> Response {
+    let secs = throttle::secs(wait);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, secs.to_string())],
+        format!("Too many failed logins; try again in {secs} seconds"),
+    )
+        .into_response()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::sqlite::SqliteStorage;
+
+    async fn memory() -> SqliteStorage {
+        config::set_for_tests();
+        SqliteStorage::memory().await
+    }
+
+    // Postgres when DATABASE_URL is set, see storage::scratch
+    async fn db() -> Db {
+        config::set_for_tests();
+        storage::scratch().await
+    }
+
+    fn state(db: &Db) -> State<AppState> {
+        State(AppState { db: db.clone(), mail_domain: None, logins: Arc::default(), verified: Arc::default() })
+    }
+
+    fn as_user(name: &str) -> Extension<CurrentUser> {
+        Extension(CurrentUser(name.to_string()))
+    }
+
+    async fn json(res: Response) -> serde_json::Value {
+        serde_json::from_slice(&axum::body::to_bytes(res.into_body(), usize::MAX).await.unwrap()).unwrap()
+    }
+
+    // The user's default board, created on first use
+    async fn main_board(db: &dyn Storage, username: &str) -> i64 {
+        user_boards(db, username).await.unwrap()[0].id
+    }
+
+    async fn add(db: &dyn Storage, board_id: i64, username: &str, title: &str, bucket: Bucket) -> Task {
+        create_task(db, board_id, username, TaskDraft::new(title.to_string(), bucket)).await.unwrap()
+    }
+
+    fn titles(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|t| t.title.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn tasks_from_before_boards_go_to_the_admin_even_when_someone_else_logs_in_first() {
+        let sqlite = memory().await;
+        // A board only to create the tasks on, then detached as if they predated boards
+        let mut tx = sqlite.begin().await.unwrap();
+        let scratch = tx.create_board("scratch", "nobody").await.unwrap();
+        tx.commit().await.unwrap();
+        for title in ["pay rent", "call mum"] {
+            add(&sqlite, scratch, "admin", title, Bucket::UrgentImportant).await;
+        }
+        sqlx::query("UPDATE tasks SET board_id = NULL").execute(sqlite.pool()).await.unwrap();
+        let db: Db = Arc::new(sqlite);
+
+        let bob = main_board(&*db, "bob").await;
+        assert!(db.tasks(bob).await.unwrap().is_empty());
+        let admin = main_board(&*db, "admin").await;
+        assert_eq!(titles(&db.tasks(admin).await.unwrap()), ["pay rent", "call mum"]);
+    }
+
+    #[test]
+    fn expected_version_from_if_match_or_body() {
+        let if_match = |v: &str| HeaderMap::from_iter([(header::IF_MATCH, HeaderValue::from_str(v).unwrap())]);
+        assert_eq!(expected_version(&if_match("\"7\""), Some(3)).unwrap(), Some(7));
+        assert_eq!(expected_version(&if_match("W/\"7\""), None).unwrap(), Some(7));
+        assert_eq!(expected_version(&if_match("*"), None).unwrap(), None);
+        assert_eq!(expected_version(&HeaderMap::new(), Some(3)).unwrap(), Some(3));
+        let status = |r: AppResult<Option<i64>>| r.unwrap_err().status();
+        assert_eq!(status(expected_version(&if_match("\"seven\""), Some(3))), StatusCode::BAD_REQUEST);
+        assert_eq!(status(expected_version(&HeaderMap::new(), None)), StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    fn move_body(id: i64, bucket: Bucket, version: Option<i64>) -> MoveBody {
+        MoveBody {
+            id,
+            bucket: bucket.as_str().to_string(),
+            ordered_ids: None,
+            source_ids: None,
+            prev_id: None,
+            next_id: None,
+            version,
+        }
+    }
+
+    async fn rename(db: &Db, id: i64, headers: HeaderMap, version: Option<i64>) -> AppResult {
+        let body = UpdateBody { title: Some("renamed".to_string()), version };
+        update_task(state(db), as_user("admin"), Path(id), headers, Json(body)).await
+    }
+
+    #[tokio::test]
+    async fn matching_version_bumps_it_and_the_etag() {
+        let db = db().await;
+        let t = add(&*db, main_board(&*db, "admin").await, "admin", "draft", Bucket::UrgentImportant).await;
+
+        let res = rename(&db, t.id, HeaderMap::new(), Some(t.version)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(res.headers()[header::ETAG], etag(t.version + 1));
+        let stored = db.task(t.id).await.unwrap().unwrap();
+        assert_eq!((stored.title.as_str(), stored.version), ("renamed", t.version + 1));
+
+        let if_match = HeaderMap::from_iter([(header::IF_MATCH, HeaderValue::from_str(&etag(stored.version)).unwrap())]);
+        let res = rename(&db, t.id, if_match, None).await.unwrap();
+        assert_eq!(res.headers()[header::ETAG], etag(t.version + 2));
+
+        let res = move_task(state(&db), as_user("admin"), HeaderMap::new(), Json(move_body(t.id, Bucket::NotUrgentImportant, Some(t.version + 2))))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(db.task(t.id).await.unwrap().unwrap().version, t.version + 3);
+    }
+
+    #[tokio::test]
+    async fn stale_version_gets_412_with_the_current_task() {
+        let db = db().await;
+        let t = add(&*db, main_board(&*db, "admin").await, "admin", "draft", Bucket::UrgentImportant).await;
+        rename(&db, t.id, HeaderMap::new(), Some(t.version)).await.unwrap();
+
+        let res = rename(&db, t.id, HeaderMap::new(), Some(t.version)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(res.headers()[header::ETAG], etag(t.version + 1));
+        let current = json(res).await;
+        assert_eq!((current["title"].as_str(), current["version"].as_i64()), (Some("renamed"), Some(t.version + 1)));
+
+        let res = move_task(state(&db), as_user("admin"), HeaderMap::new(), Json(move_body(t.id, Bucket::Today, Some(t.version))))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(json(res).await["version"].as_i64(), Some(t.version + 1));
+        // Neither change landed
+        let stored = db.task(t.id).await.unwrap().unwrap();
+        assert_eq!((stored.bucket.as_str(), stored.version), ("UrgentImportant", t.version + 1));
+    }
+
+    #[tokio::test]
+    async fn missing_version_gets_428() {
+        let db = db().await;
+        let t = add(&*db, main_board(&*db, "admin").await, "admin", "draft", Bucket::UrgentImportant).await;
+
+        let err = rename(&db, t.id, HeaderMap::new(), None).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::PRECONDITION_REQUIRED);
+        let err = move_task(state(&db), as_user("admin"), HeaderMap::new(), Json(move_body(t.id, Bucket::Today, None)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::PRECONDITION_REQUIRED);
+        assert_eq!(db.task(t.id).await.unwrap().unwrap().version, t.version);
+    }
+
+    // Titles in the bucket, in the order the page shows them
+    async fn order(db: &dyn Storage, board_id: i64, bucket: Bucket) -> Vec<String> {
+        let tasks = db.tasks(board_id).await.unwrap();
+        tasks.into_iter().filter(|t| t.bucket.as_str() == bucket.as_str()).map(|t| t.title).collect()
+    }
+
+    async fn drag(db: &Db, task: &Task, bucket: Bucket, ordered: &[&Task]) -> AppResult {
+        let mut body = move_body(task.id, bucket, None);
+        body.ordered_ids = Some(ordered.iter().map(|t| t.id).collect());
+        let if_match = HeaderMap::from_iter([(header::IF_MATCH, HeaderValue::from_static("*"))]);
+        move_task(state(db), as_user("admin"), if_match, Json(body)).await
+    }
+
+    #[tokio::test]
+    async fn colliding_keys_are_rekeyed_in_the_clients_order() {
+        let db = db().await;
+        let board = main_board(&*db, "admin").await;
+        let mut tasks = Vec::new();
+        for title in ["a", "b", "c"] {
+            tasks.push(add(&*db, board, "admin", title, Bucket::UrgentImportant).await);
+        }
+        let d = add(&*db, board, "admin", "d", Bucket::NotUrgentImportant).await;
+        // As left behind by the renumbering that predates sort keys
+        let mut tx = db.begin().await.unwrap();
+        for t in &tasks {
+            tx.rewrite_sort_key(t.id, "V").await.unwrap();
+        }
+        tx.commit().await.unwrap();
+
+        let [a, b, c] = [&tasks[0], &tasks[1], &tasks[2]];
+        drag(&db, &d, Bucket::UrgentImportant, &[a, &d, b, c]).await.unwrap();
+        assert_eq!(order(&*db, board, Bucket::UrgentImportant).await, ["a", "d", "b", "c"]);
+        let keys: Vec<String> = db.tasks(board).await.unwrap().into_iter().map(|t| t.sort_key).collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]), "{keys:?}");
+    }
+
+    async fn drop_between(db: &Db, task: &Task, bucket: Bucket, prev: Option<&Task>, next: Option<&Task>) -> AppResult {
+        let mut body = move_body(task.id, bucket, None);
+        body.prev_id = prev.map(|t| t.id);
+        body.next_id = next.map(|t| t.id);
+        let if_match = HeaderMap::from_iter([(header::IF_MATCH, HeaderValue::from_static("*"))]);
+        move_task(state(db), as_user("admin"), if_match, Json(body)).await
+    }
+
+    #[tokio::test]
+    async fn keys_squeezed_by_repeated_drops_keep_their_order_and_get_rebalanced() {
+        let db = db().await;
+        let board = main_board(&*db, "admin").await;
+        let first = add(&*db, board, "admin", "first", Bucket::UrgentImportant).await;
+        let mut below = add(&*db, board, "admin", "last", Bucket::UrgentImportant).await;
+        let mut expected = vec!["last".to_string()];
+        // Each drop lands right under `first`, halving the gap that's left
+        for i in 0..60 {
+            let t = add(&*db, board, "admin", &format!("t{i}"), Bucket::NotUrgentImportant).await;
+            drop_between(&db, &t, Bucket::UrgentImportant, Some(&first), Some(&below)).await.unwrap();
+            expected.insert(0, t.title.clone());
+            below = t;
+        }
+        expected.insert(0, "first".to_string());
+        assert_eq!(order(&*db, board, Bucket::UrgentImportant).await, expected);
+        let longest = |tasks: Vec<Task>| tasks.iter().map(|t| t.sort_key.len()).max().unwrap();
+        assert!(longest(db.tasks(board).await.unwrap()) > sort_key::REBALANCE_KEY_LEN);
+
+        rebalance_sort_keys(&*db).await.unwrap();
+        assert_eq!(order(&*db, board, Bucket::UrgentImportant).await, expected);
+        assert!(longest(db.tasks(board).await.unwrap()) <= 2);
+    }
+
+    #[tokio::test]
+    async fn neighbours_outside_the_column_are_rejected() {
+        let db = db().await;
+        let board = main_board(&*db, "admin").await;
+        let other_board = {
+            let mut tx = db.begin().await.unwrap();
+            let id = tx.create_board("Other", "admin").await.unwrap();
+            tx.commit().await.unwrap();
+            id
+        };
+        let a = add(&*db, board, "admin", "a", Bucket::UrgentImportant).await;
+        let elsewhere = add(&*db, board, "admin", "elsewhere", Bucket::NotUrgentNotImportant).await;
+        let foreign = add(&*db, other_board, "admin", "foreign", Bucket::UrgentImportant).await;
+        let t = add(&*db, board, "admin", "t", Bucket::NotUrgentImportant).await;
+
+        for (prev, next) in [(Some(&elsewhere), None), (None, Some(&foreign)), (Some(&a), Some(&foreign))] {
+            let err = drop_between(&db, &t, Bucket::UrgentImportant, prev, next).await.unwrap_err();
+            assert_eq!(err.status(), StatusCode::CONFLICT);
+        }
+        let err = drop_between(&db, &t, Bucket::UrgentImportant, Some(&t), None).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let stored = db.task(t.id).await.unwrap().unwrap();
+        assert_eq!((stored.bucket.as_str(), stored.version), ("NotUrgentImportant", t.version));
+
+        drop_between(&db, &t, Bucket::UrgentImportant, None, Some(&a)).await.unwrap();
+        assert_eq!(order(&*db, board, Bucket::UrgentImportant).await, ["t", "a"]);
+
+        // Someone else's day-plan task shows in its quadrant, so it's an acceptable (if unused) neighbour
+        let planned = add(&*db, board, "admin", "planned", Bucket::Today).await;
+        db.assign_task(planned.id, Some("bob"), "admin").await.unwrap();
+        drop_between(&db, &elsewhere, Bucket::UrgentImportant, Some(&planned), None).await.unwrap();
+        assert_eq!(order(&*db, board, Bucket::UrgentImportant).await, ["t", "a", "elsewhere"]);
+    }
+
+    #[tokio::test]
+    async fn a_move_that_fails_partway_leaves_nothing_behind() {
+        let db = db().await;
+        let board = main_board(&*db, "admin").await;
+        let a = add(&*db, board, "admin", "a", Bucket::UrgentImportant).await;
+        let b = add(&*db, board, "admin", "b", Bucket::UrgentImportant).await;
+        let c = add(&*db, board, "admin", "c", Bucket::UrgentImportant).await;
+        let t = add(&*db, board, "admin", "t", Bucket::NotUrgentImportant).await;
+        let u = add(&*db, board, "admin", "u", Bucket::NotUrgentImportant).await;
+        let snapshot = |tasks: Vec<Task>| tasks.into_iter().map(|t| (t.id, t.bucket.as_str(), t.sort_key, t.version)).collect::<Vec<_>>();
+        let before = snapshot(db.tasks(board).await.unwrap());
+
+        // The reorder of the destination is written before the version check fails
+        let mut body = move_body(t.id, Bucket::UrgentImportant, Some(t.version - 1));
+        body.ordered_ids = Some(vec![c.id, t.id, b.id, a.id]);
+        let res = move_task(state(&db), as_user("admin"), HeaderMap::new(), Json(body)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(snapshot(db.tasks(board).await.unwrap()), before);
+
+        // A source column listing a task that isn't there
+        let mut body = move_body(t.id, Bucket::UrgentImportant, Some(t.version));
+        body.ordered_ids = Some(vec![c.id, t.id, b.id, a.id]);
+        body.source_ids = Some(vec![u.id, a.id]);
+        let err = move_task(state(&db), as_user("admin"), HeaderMap::new(), Json(body)).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        assert_eq!(snapshot(db.tasks(board).await.unwrap()), before);
+    }
+
+    // The admin's board shared with bob as a viewer and carol as an editor
+    async fn shared_board(db: &Db) -> i64 {
+        let board = main_board(&**db, "admin").await;
+        for (name, role) in [("bob", Role::Viewer), ("carol", Role::Editor), ("dave", Role::Viewer)] {
+            db.create_user(name, "unused").await.unwrap();
+            db.set_member(board, name, role).await.unwrap();
+        }
+        board
+    }
+
+    #[tokio::test]
+    async fn viewers_cannot_write_and_editors_cannot_share() {
+        let db = db().await;
+        let board = shared_board(&db).await;
+        let t = add(&*db, board, "admin", "read me", Bucket::UrgentImportant).await;
+
+        for needed in [Role::Viewer, Role::Editor, Role::Owner] {
+            let allowed = require_board_role(&*db, &CurrentUser("carol".to_string()), board, needed).await.is_ok();
+            assert_eq!(allowed, needed <= Role::Editor, "{needed:?}");
+        }
+        assert!(require_board_role(&*db, &CurrentUser("bob".to_string()), board, Role::Viewer).await.is_ok());
+        let err = require_board_role(&*db, &CurrentUser("eve".to_string()), board, Role::Viewer).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+
+        let new = NewTask { title: "sneaky".to_string(), bucket: "Today".to_string(), board_id: board, quick: None };
+        let err = add_task(state(&db), as_user("bob"), Form(new)).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let err = toggle_task(state(&db), as_user("bob"), Path(t.id)).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let body = UpdateBody { title: Some("mine now".to_string()), version: None };
+        let err = update_task(state(&db), as_user("bob"), Path(t.id), HeaderMap::new(), Json(body)).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        assert!(toggle_task(state(&db), as_user("carol"), Path(t.id)).await.is_ok());
+
+        let share = |role: &str| ShareForm { username: "dave".to_string(), role: role.to_string() };
+        let err = share_board(state(&db), as_user("carol"), Path(board), Form(share("editor"))).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let err = unshare_board(state(&db), as_user("carol"), Path((board, "dave".to_string()))).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        assert_eq!(db.board_role("dave", board).await.unwrap(), Some(Role::Viewer));
+        let _ = share_board(state(&db), as_user("admin"), Path(board), Form(share("editor"))).await.unwrap();
+        assert_eq!(db.board_role("dave", board).await.unwrap(), Some(Role::Editor));
+    }
+
+    #[tokio::test]
+    async fn a_today_task_shows_in_its_assignees_today_and_in_its_quadrant_for_everyone_else() {
+        let db = db().await;
+        let board = shared_board(&db).await;
+        add(&*db, board, "carol", "quadrant", Bucket::NotUrgentImportant).await;
+        let planned = add(&*db, board, "carol", "carol's plan", Bucket::Today).await;
+        let mut tx = db.begin().await.unwrap();
+        tx.reclassify(planned.id, TaskType::NotUrgentImportant, "carol", None).await.unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(db.task(planned.id).await.unwrap().unwrap().assignee.as_deref(), Some("carol"));
+
+        let columns = |groups: BTreeMap<&'static str, Vec<Task>>| {
+            groups.into_iter().map(|(column, tasks)| (column, titles(&tasks).join(", "))).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            columns(fetch_all_grouped(&*db, board, "carol").await.unwrap()),
+            [("NotUrgentImportant", "quadrant".to_string()), ("Today", "carol's plan".to_string())]
+        );
+        for viewer in ["admin", "bob"] {
+            // After the quadrant's own tasks
+            assert_eq!(
+                columns(fetch_all_grouped(&*db, board, viewer).await.unwrap()),
+                [("NotUrgentImportant", "quadrant, carol's plan".to_string())],
+                "{viewer}"
+            );
+        }
+    }
+
+    async fn bulk(db: &Db, username: &str, action: &str, ids: Vec<i64>) -> AppResult {
+        let body = BulkBody { action: action.to_string(), ids, bucket: None, add_tags: Vec::new(), remove_tags: Vec::new() };
+        bulk_tasks(state(db), as_user(username), Json(body)).await
+    }
+
+    // id -> the error reported for it, None when it went through
+    async fn outcomes(res: Response) -> Vec<(i64, Option<String>)> {
+        let results = json(res).await["results"].as_array().unwrap().clone();
+        results
+            .iter()
+            .map(|r| {
+                assert_eq!(r["ok"].as_bool(), Some(r.get("error").is_none()), "{r}");
+                (r["id"].as_i64().unwrap(), r.get("error").map(|e| e.as_str().unwrap().to_string()))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn bulk_reports_each_id_and_applies_the_rest() {
+        let db = db().await;
+        let board = shared_board(&db).await;
+        let bobs_board = main_board(&*db, "bob").await;
+        let mine = add(&*db, board, "admin", "mine", Bucket::UrgentImportant).await;
+        let done = add(&*db, board, "admin", "done", Bucket::UrgentImportant).await;
+        db.toggle_completed(done.id, "admin").await.unwrap();
+        let other = add(&*db, board, "carol", "carol's", Bucket::NotUrgentImportant).await;
+        let hidden = add(&*db, bobs_board, "bob", "bob's", Bucket::UrgentImportant).await;
+
+        let res = bulk(&db, "admin", "complete", vec![mine.id, done.id, hidden.id, 9999, other.id]).await.unwrap();
+        let error = |e: &str| Some(e.to_string());
+        assert_eq!(
+            outcomes(res).await,
+            [(mine.id, None), (done.id, error("already completed")), (hidden.id, error("not found")), (9999, error("not found")), (other.id, None)]
+        );
+        for id in [mine.id, done.id, other.id] {
+            assert!(db.task(id).await.unwrap().unwrap().completed, "{id}");
+        }
+        assert!(!db.task(hidden.id).await.unwrap().unwrap().completed);
+
+        // bob only views the shared board
+        let res = bulk(&db, "bob", "delete", vec![hidden.id, mine.id]).await.unwrap();
+        assert_eq!(outcomes(res).await, [(hidden.id, None), (mine.id, error("forbidden"))]);
+        assert!(db.task(hidden.id).await.unwrap().is_none());
+        assert!(db.task(mine.id).await.unwrap().is_some());
+
+        let err = bulk(&db, "admin", "complete", vec![mine.id, mine.id]).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    async fn incoming(db: &Db, token: Option<&str>, title: &str, external_id: Option<&str>) -> AppResult {
+        let headers = HeaderMap::from_iter(token.map(|t| (header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {t}")).unwrap())));
+        let body = IncomingTask { title: title.to_string(), quadrant: None, notes: None, external_id: external_id.map(str::to_string) };
+        incoming_task(state(db), headers, Json(body)).await
+    }
+
+    #[tokio::test]
+    async fn incoming_tasks_are_deduplicated_by_external_id() {
+        let db = db().await;
+        let board = main_board(&*db, "admin").await;
+        db.create_incoming_token(board, "ci", &hex::encode(Sha256::digest(b"tok")), "admin").await.unwrap();
+
+        let res = incoming(&db, Some("tok"), "build failed", Some("build-1")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let first = json(res).await["id"].as_i64().unwrap();
+        db.toggle_completed(first, "admin").await.unwrap();
+
+        let res = incoming(&db, Some("tok"), "build failed again", Some("build-1")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(json(res).await, serde_json::json!({ "id": first, "created": false }));
+        let task = db.task(first).await.unwrap().unwrap();
+        assert_eq!((task.title.as_str(), task.completed), ("build failed again", false));
+
+        let res = incoming(&db, Some("tok"), "build failed", Some("build-2")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let res = incoming(&db, Some("tok"), "no id", None).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(db.tasks(board).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn incoming_tasks_need_a_live_token() {
+        let db = db().await;
+        let board = shared_board(&db).await;
+        db.create_incoming_token(board, "ci", &hex::encode(Sha256::digest(b"tok")), "carol").await.unwrap();
+
+        for token in [None, Some(""), Some("wrong")] {
+            let res = incoming(&db, token, "spam", None).await.unwrap();
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{token:?}");
+            assert_eq!(res.headers()[header::WWW_AUTHENTICATE], "Bearer");
+        }
+        assert_eq!(incoming(&db, Some("tok"), "ok", None).await.unwrap().status(), StatusCode::CREATED);
+        // Its creator can only view the board now
+        db.set_member(board, "carol", Role::Viewer).await.unwrap();
+        assert_eq!(incoming(&db, Some("tok"), "spam", None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(titles(&db.tasks(board).await.unwrap()), ["ok"]);
+    }
+
+    #[tokio::test]
+    async fn webhooks_only_go_to_public_addresses() {
+        let db = db().await;
+        let board = main_board(&*db, "bob").await;
+        let add = |url: &str| {
+            let form = WebhookForm { url: url.to_string(), events: String::new(), task_type: String::new(), secret: String::new() };
+            create_webhook(state(&db), as_user("bob"), Path(board), Form(form))
+        };
+        for url in ["http://127.0.0.1:8080/hook", "http://localhost/", "https://10.0.0.5/", "http://169.254.169.254/latest/meta-data", "http://[::1]/", "http://[::ffff:192.168.0.1]/", "ftp://203.0.114.7/"] {
+            let err = add(url).await.unwrap_err();
+            assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY, "{url}");
+        }
+        assert!(db.webhooks(board).await.unwrap().is_empty());
+        assert!(add("https://93.184.215.14/hook").await.is_ok());
+        assert_eq!(db.webhooks(board).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deleting_a_board_takes_its_tasks_and_members_but_never_the_last_one() {
+        let db = db().await;
+        let first = main_board(&*db, "bob").await;
+        let err = delete_board(state(&db), as_user("bob"), Path(first)).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let mut tx = db.begin().await.unwrap();
+        let second = tx.create_board("side project", "bob").await.unwrap();
+        tx.add_member(second, "carol", Role::Editor).await.unwrap();
+        tx.commit().await.unwrap();
+        let t = add(&*db, second, "bob", "sketch logo", Bucket::UrgentImportant).await;
+        assert!(delete_board(state(&db), as_user("carol"), Path(second)).await.is_err());
+
+        assert!(delete_board(state(&db), as_user("bob"), Path(second)).await.is_ok());
+        assert!(db.task(t.id).await.unwrap().is_none());
+        assert!(db.members(second).await.unwrap().is_empty());
+        assert_eq!(user_boards(&*db, "bob").await.unwrap().iter().map(|b| b.id).collect::<Vec<_>>(), [first]);
+    }
+}
@@ -0,0 +1,152 @@
+// Password hashes for the users table: Argon2id PHC strings ("$argon2id$v=19$...").
+//
+// Basic auth sends the password with every request, and an Argon2 check is
+// slow on purpose, so it runs on the blocking pool and a success is
+// remembered for CACHE_TTL. The cache holds a keyed SHA-256 of the password,
+// never the password itself; a changed password can keep working for at most
+// that long.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+pub fn hash(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("argon2 accepts any password with default params")
+        .to_string()
+}
+
+pub fn verify(password: &str, stored: &str) -> bool {
+    PasswordHash::new(stored).is_ok_and(|h| Argon2::default().verify_password(password.as_bytes(), &h).is_ok())
+}
+
+// `verify` off the async workers
+pub async fn verify_blocking(password: &str, stored: String) -> bool {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || verify(&password, &stored)).await.unwrap_or(false)
+}
+
+// Constant-time comparison for the configured admin password
+pub fn matches(password: &str, expected: &str) -> bool {
+    password.as_bytes().ct_eq(expected.as_bytes()).into()
+}
+
+// Recently verified logins: username -> (digest of the password, when it expires)
+pub struct Cache {
+    key: [u8; 32],
+    entries: Mutex<HashMap<String, ([u8; 32], Instant)>>,
+}
+
+impl Default for Cache {
+    fn default() -> Cache {
+        Cache { key: rand::random(), entries: Mutex::default() }
+    }
+}
+
+impl Cache {
+    pub fn contains(&self, username: &str, password: &str) -> bool {
+        self.contains_at(username, password, Instant::now())
+    }
+
+    pub fn insert(&self, username: &str, password: &str) {
+        self.insert_at(username, password, Instant::now())
+    }
+
+    fn digest(&self, username: &str, password: &str) -> [u8; 32] {
+        Sha256::new()
+            .chain_update(self.key)
+            .chain_update(username.as_bytes())
+            .chain_update([0])
+            .chain_update(password.as_bytes())
+            .finalize()
+            .into()
+    }
+
+    fn contains_at(&self, username: &str, password: &str, now: Instant) -> bool {
+        let digest = self.digest(username, password);
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(username)
+            .is_some_and(|(cached, expires)| now < *expires && bool::from(cached[..].ct_eq(&digest[..])))
+    }
+
+    fn insert_at(&self, username: &str, password: &str, now: Instant) {
+        let digest = self.digest(username, password);
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (_, expires)| now < *expires);
+        entries.insert(username.to_string(), (digest, now + CACHE_TTL));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argon2_round_trip() {
+        let stored = hash("hunter2");
+        assert!(stored.starts_with("$argon2id$"));
+        assert!(verify("hunter2", &stored));
+        assert!(!verify("hunter3", &stored));
+        assert_ne!(stored, hash("hunter2"), "salted");
+    }
+
+    #[test]
+    fn garbage_never_verifies() {
+        for stored in ["", "sha256$", "sha256$00$00", "plain", "$argon2id$nope"] {
+            assert!(!verify("", stored), "{stored:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn verifies_on_the_blocking_pool() {
+        let stored = hash("hunter2");
+        assert!(verify_blocking("hunter2", stored.clone()).await);
+        assert!(!verify_blocking("hunter3", stored).await);
+    }
+
+    #[test]
+    fn admin_password_match() {
+        assert!(matches("password", "password"));
+        assert!(!matches("passwore", "password"));
+        assert!(!matches("pass", "password"));
+        assert!(!matches("", "password"));
+    }
+
+    #[test]
+    fn cache_remembers_the_password_until_it_expires() {
+        let cache = Cache::default();
+        let now = Instant::now();
+        assert!(!cache.contains_at("alice", "hunter2", now));
+        cache.insert_at("alice", "hunter2", now);
+        assert!(cache.contains_at("alice", "hunter2", now + CACHE_TTL - Duration::from_secs(1)));
+        assert!(!cache.contains_at("alice", "hunter3", now));
+        assert!(!cache.contains_at("bob", "hunter2", now));
+        assert!(!cache.contains_at("alice", "hunter2", now + CACHE_TTL));
+    }
+
+    #[test]
+    fn cache_keeps_one_password_per_user_and_drops_expired_ones() {
+        let cache = Cache::default();
+        let now = Instant::now();
+        cache.insert_at("alice", "old", now);
+        cache.insert_at("alice", "new", now);
+        assert!(!cache.contains_at("alice", "old", now));
+        assert!(cache.contains_at("alice", "new", now));
+        cache.insert_at("bob", "pw", now + CACHE_TTL);
+        assert_eq!(cache.entries.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,181 @@
+// Quick-add syntax: one line of text that says where a task goes.
+//
+//   "Call Bob !u !i #work due:fri"  -> Urgent & Important, tagged work, due Friday
+//   "Book flights @today"           -> Today's Tasks
+//
+// Markers can appear anywhere; whatever isn't a marker is the title. A marker
+// that doesn't parse (e.g. "due:someday") is left in the title untouched.
+
+use chrono::{Datelike, Days, Duration, NaiveDate, Weekday};
+
+use crate::{normalize_tag, Bucket, TaskType};
+
+#[derive(Debug, Default)]
+pub struct QuickAdd {
+    pub title: String,
+    pub urgent: bool,
+    pub important: bool,
+    pub today: bool,
+    pub tags: Vec<String>,
+    pub due: Option<NaiveDate>,
+}
+
+impl QuickAdd {
+    pub fn task_type(&self) -> TaskType {
+        match (self.urgent, self.important) {
+            (true, true) => TaskType::UrgentImportant,
+            (true, false) => TaskType::UrgentNotImportant,
+            (false, true) => TaskType::NotUrgentImportant,
+            (false, false) => TaskType::NotUrgentNotImportant,
+        }
+    }
+
+    pub fn bucket(&self) -> Bucket {
+        if self.today {
+            Bucket::Today
+        } else {
+            self.task_type().bucket()
+        }
+    }
+}
+
+/// Split `input` into markers and title; `today` anchors relative due dates.
+pub fn parse(input: &str, today: NaiveDate) -> QuickAdd {
+    let mut q = QuickAdd::default();
+    let mut words = Vec::new();
+    for word in input.split_whitespace() {
+        let lower = word.to_lowercase();
+        match lower.as_str() {
+            "!u" => q.urgent = true,
+            "!i" => q.important = true,
+            "!ui" | "!iu" => {
+                q.urgent = true;
+                q.important = true;
+            }
+            "@today" => q.today = true,
+            _ => {
+                if let Some(tag) = lower.strip_prefix('#').and_then(normalize_tag) {
+                    if !q.tags.contains(&tag) {
+                        q.tags.push(tag);
+                    }
+                } else if let Some(due) = lower.strip_prefix("due:").and_then(|d| parse_due(d, today)) {
+                    q.due = Some(due);
+                } else {
+                    words.push(word);
+                }
+            }
+        }
+    }
+    q.title = words.join(" ");
+    q
+}
+
+// "today", "tomorrow"/"tom", weekday names ("fri", "friday": the next one,
+// today included), "+3" or "+3d" days from now, or an ISO date.
+fn parse_due(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match s {
+        "today" => return Some(today),
+        "tomorrow" | "tom" => return today.succ_opt(),
+        _ => {}
+    }
+    if let Some(n) = s.strip_prefix('+') {
+        let days: i64 = n.trim_end_matches('d').parse().ok()?;
+        // Out of chrono's range counts as not a date, so the marker stays in the title
+        return today.checked_add_signed(Duration::try_days(days)?);
+    }
+    if let Some(weekday) = parse_weekday(s) {
+        let ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+        return today.checked_add_days(Days::new(ahead.into()));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    const DAYS: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Mon),
+        ("tuesday", Weekday::Tue),
+        ("wednesday", Weekday::Wed),
+        ("thursday", Weekday::Thu),
+        ("friday", Weekday::Fri),
+        ("saturday", Weekday::Sat),
+        ("sunday", Weekday::Sun),
+    ];
+    if s.len() < 3 {
+        return None;
+    }
+    DAYS.iter().find(|(name, _)| name.starts_with(s)).map(|&(_, day)| day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    // A Wednesday
+    const TODAY: &str = "2024-05-15";
+
+    #[test]
+    fn markers_anywhere_rest_is_title() {
+        let q = parse("Call !u Bob #Work !i due:fri #work", date(TODAY));
+        assert_eq!(q.title, "Call Bob");
+        assert!(q.urgent && q.important && !q.today);
+        assert_eq!(q.tags, ["work"]);
+        assert_eq!(q.due, Some(date("2024-05-17")));
+        assert!(matches!(q.bucket(), Bucket::UrgentImportant));
+    }
+
+    #[test]
+    fn today_marker_picks_today_column() {
+        let q = parse("Book flights @today !i", date(TODAY));
+        assert!(matches!(q.bucket(), Bucket::Today));
+        assert!(matches!(q.task_type(), TaskType::NotUrgentImportant));
+    }
+
+    #[test]
+    fn unknown_markers_stay_in_title() {
+        let q = parse("Plan due:someday !x #", date(TODAY));
+        assert_eq!(q.title, "Plan due:someday !x #");
+        assert_eq!(q.due, None);
+        assert!(q.tags.is_empty());
+    }
+
+    #[test]
+    fn weekdays_are_the_next_one_today_included() {
+        let today = date(TODAY);
+        assert_eq!(parse_due("wed", today), Some(today));
+        assert_eq!(parse_due("wednesday", today), Some(today));
+        assert_eq!(parse_due("thu", today), Some(date("2024-05-16")));
+        assert_eq!(parse_due("mon", today), Some(date("2024-05-20")));
+        assert_eq!(parse_due("tu", today), None); // too short to tell tue from thu
+    }
+
+    #[test]
+    fn relative_and_named_days() {
+        let today = date(TODAY);
+        assert_eq!(parse_due("today", today), Some(today));
+        assert_eq!(parse_due("tom", today), Some(date("2024-05-16")));
+        assert_eq!(parse_due("+3", today), Some(date("2024-05-18")));
+        assert_eq!(parse_due("+3d", today), Some(date("2024-05-18")));
+        assert_eq!(parse_due("+x", today), None);
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert_eq!(parse_due("2025-01-02", date(TODAY)), Some(date("2025-01-02")));
+        assert_eq!(parse_due("2025-02-30", date(TODAY)), None);
+    }
+
+    #[test]
+    fn huge_offsets_are_not_dates() {
+        let today = date(TODAY);
+        assert_eq!(parse_due("+100000000", today), None);
+        assert_eq!(parse_due(&format!("+{}", i64::MAX), today), None);
+        assert_eq!(parse_due(&format!("+{}", i64::MIN), today), None);
+        let q = parse("Later due:+100000000", today);
+        assert_eq!(q.title, "Later due:+100000000");
+        assert_eq!(q.due, None);
+    }
+}
@@ -0,0 +1,391 @@
+// Minimal SMTP receiver that turns incoming mail into tasks.
+//
+// Enabled by setting EISENHOWER_SMTP_BIND (e.g. "0.0.0.0:2525"). Each user
+// gets a secret address, `<secret>@<domain>`; anything sent to an address
+// that isn't a known secret is refused at RCPT time, so random spam never
+// reaches a board. Plus-addressing picks the column: `<secret>+ui@...`,
+// `+uni`, `+nui`, `+nun` or `+today`. Without it the subject's quick-add
+// markers (`!u`, `!i`, `@today`, `#tag`, `due:fri`) decide, as in the
+// quick-add box. The subject becomes the title and the plain-text body the
+// task's notes; tasks land on the recipient's first board.
+//
+// Only the bits of the protocol a forwarding MTA needs are implemented: no
+// TLS, no AUTH, no pipelining. Put it behind your real mail server.
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use mail_parser::MessageParser;
+use rand::{distributions::Alphanumeric, Rng};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::Semaphore,
+};
+use tracing::{info, warn};
+
+use crate::{
+    config, create_task, parse_quadrant, quick_add,
+    storage::{Db, Storage},
+    user_boards, validate, Bucket, TaskDraft,
+};
+
+const MAX_MESSAGE_BYTES: usize = 1024 * 1024;
+const MAX_LINE_BYTES: u64 = 4096; // including the CRLF, for commands and message lines alike
+const MAX_RECIPIENTS: usize = 20;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+const MAX_SESSIONS: usize = 32; // each may buffer up to MAX_MESSAGE_BYTES
+
+pub async fn serve(db: Db, addr: SocketAddr, domain: String) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(?addr, "smtp listening");
+    let sessions = Arc::new(Semaphore::new(MAX_SESSIONS));
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let Ok(permit) = sessions.clone().try_acquire_owned() else {
+            // Best effort and never blocking: the sender retries either way
+            let _ = stream.try_write(b"421 4.3.2 Too many connections, try again later\r\n");
+            warn!(%peer, "smtp connection refused, too many sessions");
+            continue;
+        };
+        let db = db.clone();
+        let domain = domain.clone();
+        tokio::spawn(async move {
+            let (reader, writer) = stream.into_split();
+            if let Err(e) = session(BufReader::new(reader), writer, &*db, &domain).await {
+                warn!(%peer, error = %e, "smtp session ended with an error");
+            }
+            drop(permit);
+        });
+    }
+}
+
+// Who a message is for, resolved from the RCPT address
+struct Recipient {
+    username: String,
+    bucket: Option<Bucket>,
+}
+
+async fn session(
+    mut reader: impl AsyncBufRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    db: &dyn Storage,
+    domain: &str,
+) -> anyhow::Result<()> {
+    writer.write_all(format!("220 {domain} eisenpower ESMTP\r\n").as_bytes()).await?;
+
+    let mut recipients: Vec<Recipient> = Vec::new();
+    let mut sender = false;
+    loop {
+        let line = match read_line(&mut reader).await? {
+            Line::Eof => return Ok(()),
+            Line::TooLong => {
+                writer.write_all(b"500 Line too long\r\n").await?;
+                continue;
+            }
+            Line::Full(line) => String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n']).to_string(),
+        };
+        let (verb, arg) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+        let reply = match verb.to_ascii_uppercase().as_str() {
+            "HELO" => format!("250 {domain}\r\n"),
+            "EHLO" => format!("250-{domain}\r\n250-8BITMIME\r\n250 SIZE {MAX_MESSAGE_BYTES}\r\n"),
+            "MAIL" => {
+                sender = true;
+                recipients.clear();
+                "250 OK\r\n".to_string()
+            }
+            "RCPT" if !sender => "503 MAIL first\r\n".to_string(),
+            "RCPT" if recipients.len() >= MAX_RECIPIENTS => "452 Too many recipients\r\n".to_string(),
+            "RCPT" => match resolve_recipient(db, arg).await {
+                Ok(Some(r)) => {
+                    recipients.push(r);
+                    "250 OK\r\n".to_string()
+                }
+                Ok(None) => "550 No such mailbox\r\n".to_string(),
+                // Temporary, so the sender retries instead of bouncing
+                Err(e) => {
+                    warn!(error = %e, "could not look up recipient");
+                    "451 4.3.0 Mailbox lookup failed, try again later\r\n".to_string()
+                }
+            },
+            "DATA" if recipients.is_empty() => "503 RCPT first\r\n".to_string(),
+            "DATA" => {
+                writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await?;
+                let reply = match read_data(&mut reader).await? {
+                    Data::Message(raw) => match deliver(db, &recipients, &raw).await {
+                        Ok(()) => "250 OK\r\n",
+                        Err(e) => {
+                            warn!(error = %e, "could not turn mail into a task");
+                            "451 Could not store message\r\n"
+                        }
+                    },
+                    Data::TooLarge => "552 Message too large\r\n",
+                    Data::LineTooLong => "500 Line too long\r\n",
+                };
+                sender = false;
+                recipients.clear();
+                reply.to_string()
+            }
+            "RSET" => {
+                sender = false;
+                recipients.clear();
+                "250 OK\r\n".to_string()
+            }
+            "NOOP" => "250 OK\r\n".to_string(),
+            "QUIT" => {
+                writer.write_all(b"221 Bye\r\n").await?;
+                return Ok(());
+            }
+            _ => "502 Command not implemented\r\n".to_string(),
+        };
+        writer.write_all(reply.as_bytes()).await?;
+    }
+}
+
+enum Line {
+    Full(Vec<u8>), // with its line ending
+    TooLong,
+    Eof,
+}
+
+// One line, never buffering more than MAX_LINE_BYTES of it. The rest of an
+// overlong line is read and thrown away so the next read starts on a new line.
+async fn read_line(reader: &mut (impl AsyncBufRead + Unpin)) -> anyhow::Result<Line> {
+    let mut buf = Vec::new();
+    let n = tokio::time::timeout(IDLE_TIMEOUT, (&mut *reader).take(MAX_LINE_BYTES + 1).read_until(b'\n', &mut buf)).await??;
+    if n == 0 {
+        return Ok(Line::Eof);
+    }
+    if n as u64 <= MAX_LINE_BYTES {
+        return Ok(Line::Full(buf));
+    }
+    if buf.ends_with(b"\n") {
+        return Ok(Line::TooLong);
+    }
+    loop {
+        buf.clear();
+        let n = tokio::time::timeout(IDLE_TIMEOUT, (&mut *reader).take(MAX_LINE_BYTES).read_until(b'\n', &mut buf)).await??;
+        if n == 0 || buf.ends_with(b"\n") {
+            return Ok(Line::TooLong);
+        }
+    }
+}
+
+enum Data {
+    Message(Vec<u8>),
+    TooLarge,
+    LineTooLong,
+}
+
+// The message up to the lone "." line, with dot-stuffing undone. A message
+// over the size cap or with an overlong line is read to the end (so the
+// session stays in sync) and dropped.
+async fn read_data(reader: &mut (impl AsyncBufRead + Unpin)) -> anyhow::Result<Data> {
+    let mut raw = Vec::new();
+    let mut refused = None;
+    loop {
+        let line = match read_line(reader).await? {
+            Line::Eof => anyhow::bail!("connection closed during DATA"),
+            Line::TooLong => {
+                refused.get_or_insert(Data::LineTooLong);
+                continue;
+            }
+            Line::Full(line) => line,
+        };
+        if line == b".\r\n" || line == b".\n" {
+            break;
+        }
+        let line = line.strip_prefix(b".").unwrap_or(&line);
+        if raw.len() + line.len() > MAX_MESSAGE_BYTES {
+            refused.get_or_insert(Data::TooLarge);
+        } else if refused.is_none() {
+            raw.extend_from_slice(line);
+        }
+    }
+    Ok(refused.unwrap_or(Data::Message(raw)))
+}
+
+// "TO:<k3j9x2q8+ui@tasks.example.com> SIZE=123" -> the secret's owner and bucket.
+// Ok(None) when the address is malformed or nobody owns the secret.
+async fn resolve_recipient(db: &dyn Storage, arg: &str) -> sqlx::Result<Option<Recipient>> {
+    let Some((secret, bucket)) = parse_recipient(arg) else {
+        return Ok(None);
+    };
+    Ok(db.inbox_owner(&secret).await?.map(|username| Recipient { username, bucket }))
+}
+
+fn parse_recipient(arg: &str) -> Option<(String, Option<Bucket>)> {
+    let addr = arg.split_once(':')?.1.trim();
+    let addr = addr.trim_start_matches('<').split('>').next()?;
+    let local = addr.split('@').next()?.to_lowercase();
+    let (secret, tag) = match local.split_once('+') {
+        Some((secret, tag)) => (secret.to_string(), Some(tag.to_string())),
+        None => (local, None),
+    };
+    let bucket = match tag.as_deref() {
+        None => None,
+        Some(tag) => Some(parse_quadrant(tag)?),
+    };
+    Some((secret, bucket))
+}
+
+async fn deliver(db: &dyn Storage, recipients: &[Recipient], raw: &[u8]) -> anyhow::Result<()> {
+    let message = MessageParser::default()
+        .parse(raw)
+        .ok_or_else(|| anyhow::anyhow!("unparseable message"))?;
+    let subject = message.subject().unwrap_or_default();
+    let notes = message.body_text(0).map(|body| {
+        let body = body.trim();
+        body.chars().take(validate::MAX_NOTES_CHARS).collect::<String>()
+    });
+
+    for r in recipients {
+        let q = quick_add::parse(subject, config::today());
+        let mut draft = TaskDraft::from_quick_add(q);
+        if draft.title.is_empty() {
+            draft.title = "(no subject)".to_string();
+        }
+        // Mail can't be answered with a 422, so an overlong subject is cut instead
+        draft.title = draft.title.chars().take(validate::MAX_TITLE_CHARS).collect();
+        if let Some(bucket) = r.bucket {
+            draft.bucket = bucket;
+            if !matches!(bucket, Bucket::Today) {
+                draft.task_type = crate::TaskType::from_bucket(bucket);
+            }
+        }
+        draft.notes = notes.clone().filter(|n| !n.is_empty());
+        let boards = user_boards(db, &r.username).await?;
+        let board = boards.first().ok_or_else(|| anyhow::anyhow!("{} has no board", r.username))?;
+        let task = create_task(db, board.id, &r.username, draft).await?;
+        info!(user = %r.username, task = task.id, "task created from mail");
+    }
+    Ok(())
+}
+
+/// A fresh random inbox secret: lowercase so it survives case-folding mail servers.
+pub fn new_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(|c| (c as char).to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStorage;
+
+    fn line(len: usize) -> Vec<u8> {
+        let mut line = vec![b'a'; len - 2];
+        line.extend_from_slice(b"\r\n");
+        line
+    }
+
+    #[tokio::test]
+    async fn lines_up_to_the_limit_are_read_whole() {
+        let input = [line(MAX_LINE_BYTES as usize), b"QUIT\r\n".to_vec()].concat();
+        let mut reader = input.as_slice();
+        assert!(matches!(read_line(&mut reader).await.unwrap(), Line::Full(l) if l.len() == MAX_LINE_BYTES as usize));
+        assert!(matches!(read_line(&mut reader).await.unwrap(), Line::Full(l) if l == b"QUIT\r\n"));
+        assert!(matches!(read_line(&mut reader).await.unwrap(), Line::Eof));
+    }
+
+    #[tokio::test]
+    async fn an_overlong_line_is_skipped_and_the_next_one_read() {
+        for len in [MAX_LINE_BYTES as usize + 1, 3 * MAX_LINE_BYTES as usize] {
+            let input = [line(len), b"QUIT\r\n".to_vec()].concat();
+            let mut reader = input.as_slice();
+            assert!(matches!(read_line(&mut reader).await.unwrap(), Line::TooLong), "{len}");
+            assert!(matches!(read_line(&mut reader).await.unwrap(), Line::Full(l) if l == b"QUIT\r\n"), "{len}");
+        }
+    }
+
+    #[tokio::test]
+    async fn data_is_unstuffed_up_to_the_lone_dot() {
+        let mut reader: &[u8] = b"Subject: hi\r\n\r\n..leading dot\r\n.\r\nQUIT\r\n";
+        assert!(matches!(read_data(&mut reader).await.unwrap(), Data::Message(m) if m == b"Subject: hi\r\n\r\n.leading dot\r\n"));
+        assert_eq!(reader, b"QUIT\r\n");
+    }
+
+    #[tokio::test]
+    async fn refused_data_is_still_read_to_the_end() {
+        let input = [b"Subject: hi\r\n".to_vec(), line(MAX_LINE_BYTES as usize * 2), b".\r\nQUIT\r\n".to_vec()].concat();
+        let mut reader = input.as_slice();
+        assert!(matches!(read_data(&mut reader).await.unwrap(), Data::LineTooLong));
+        assert_eq!(reader, b"QUIT\r\n");
+
+        let body = line(1000).repeat(MAX_MESSAGE_BYTES / 1000 + 1);
+        let input = [body, b".\r\nQUIT\r\n".to_vec()].concat();
+        let mut reader = input.as_slice();
+        assert!(matches!(read_data(&mut reader).await.unwrap(), Data::TooLarge));
+        assert_eq!(reader, b"QUIT\r\n");
+
+        let mut reader: &[u8] = b"Subject: hi\r\n";
+        assert!(read_data(&mut reader).await.is_err());
+    }
+
+    // The replies to a whole session, one per line
+    async fn replies(db: &dyn Storage, input: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        session(input.as_bytes(), &mut output, db, "tasks.example.com").await.unwrap();
+        String::from_utf8(output).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn recipients_are_looked_up_by_secret() {
+        let db = crate::storage::scratch().await;
+        db.inbox_secret("alice", "k3j9x2q8").await.unwrap();
+        let input = "HELO x\r\nMAIL FROM:<a@b>\r\nRCPT TO:<K3J9X2Q8+ui@tasks.example.com>\r\nRCPT TO:<k3j9x2q8+xx@tasks.example.com>\r\nRCPT TO:<nobody@tasks.example.com>\r\nQUIT\r\n";
+        let replies = replies(&*db, input).await;
+        assert_eq!(replies[3], "250 OK");
+        assert!(replies[4].starts_with("550 "), "{replies:?}");
+        assert!(replies[5].starts_with("550 "), "{replies:?}");
+    }
+
+    #[tokio::test]
+    async fn a_failed_lookup_is_a_temporary_error() {
+        let db = SqliteStorage::memory().await;
+        db.pool().close().await;
+        let replies = replies(&db, "HELO x\r\nMAIL FROM:<a@b>\r\nRCPT TO:<k3j9x2q8@tasks.example.com>\r\nQUIT\r\n").await;
+        assert!(replies[3].starts_with("451 4.3.0 "), "{replies:?}");
+    }
+
+    #[tokio::test]
+    async fn mail_becomes_a_task_in_the_addressed_column() {
+        config::set_for_tests();
+        let db = crate::storage::scratch().await;
+        db.inbox_secret("alice", "k3j9x2q8").await.unwrap();
+        let mail = |to: &str, subject: &str| {
+            format!("MAIL FROM:<a@b>\r\nRCPT TO:<{to}@tasks.example.com>\r\nDATA\r\nSubject: {subject}\r\n\r\nSee the letter.\r\n.\r\n")
+        };
+        let input = [
+            "HELO x\r\n".to_string(),
+            mail("k3j9x2q8+ui", "Call the bank #Finance"),
+            mail("k3j9x2q8", "Renew passport !i @today"),
+            mail("k3j9x2q8+today", "Water plants !u"),
+            "QUIT\r\n".to_string(),
+        ]
+        .concat();
+        let replies = replies(&*db, &input).await;
+        assert_eq!(replies.iter().filter(|r| *r == "250 OK").count(), 9, "{replies:?}");
+
+        let board = user_boards(&*db, "alice").await.unwrap()[0].id;
+        let tasks = db.tasks(board).await.unwrap();
+        let task = |title: &str| tasks.iter().find(|t| t.title == title).unwrap_or_else(|| panic!("no {title:?} in {tasks:?}"));
+
+        let bank = task("Call the bank");
+        assert!(matches!(bank.bucket, Bucket::UrgentImportant));
+        assert_eq!(bank.tags, ["finance"]);
+        assert_eq!(bank.notes.as_deref(), Some("See the letter."));
+        assert_eq!(bank.created_by.as_deref(), Some("alice"));
+
+        let passport = task("Renew passport");
+        assert!(matches!(passport.bucket, Bucket::Today));
+        assert!(matches!(passport.task_type, crate::TaskType::NotUrgentImportant));
+
+        let plants = task("Water plants");
+        assert!(matches!(plants.bucket, Bucket::Today));
+        assert!(matches!(plants.task_type, crate::TaskType::UrgentNotImportant));
+        assert!(plants.tags.is_empty());
+    }
+}
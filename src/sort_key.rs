@@ -0,0 +1,170 @@
+// Fractional sort keys for ordering tasks within a bucket.
+//
+// A key is a base-62 fraction written with digits in ASCII order, so comparing
+// keys as strings compares them as numbers. There is always room for another
+// key between two existing ones, so a move rewrites only the moved row. Keys
+// never end in the zero digit; otherwise "A" and "A0" would be equal in value
+// and nothing would fit between them.
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: usize = DIGITS.len();
+
+// Keys longer than this get their bucket rewritten by `spread`
+pub const REBALANCE_KEY_LEN: usize = 6;
+
+fn digit_value(c: u8) -> usize {
+    DIGITS.iter().position(|&d| d == c).unwrap_or(0)
+}
+
+/// A key strictly between `lo` and `hi`; `None` means the start or end of the list.
+/// Falls back to a key after `lo` when the bounds are out of order.
+pub fn between(lo: Option<&str>, hi: Option<&str>) -> String {
+    let lo = lo.unwrap_or("").as_bytes();
+    let hi = hi.map(str::as_bytes).filter(|hi| *hi > lo);
+    let mut key = Vec::new();
+    midpoint(lo, hi, &mut key);
+    String::from_utf8(key).unwrap_or_default()
+}
+
+fn midpoint(lo: &[u8], hi: Option<&[u8]>, out: &mut Vec<u8>) {
+    if let Some(hi) = hi {
+        // Copy the shared prefix (treating a short `lo` as zero-padded) and recurse on the rest
+        let n = hi
+            .iter()
+            .enumerate()
+            .take_while(|&(i, &h)| lo.get(i).copied().unwrap_or(DIGITS[0]) == h)
+            .count();
+        if n > 0 {
+            out.extend_from_slice(&hi[..n]);
+            return midpoint(lo.get(n..).unwrap_or(&[]), Some(&hi[n..]), out);
+        }
+    }
+    let d_lo = lo.first().map(|&c| digit_value(c)).unwrap_or(0);
+    let d_hi = hi.map(|hi| digit_value(hi[0])).unwrap_or(BASE);
+    if d_hi.saturating_sub(d_lo) > 1 {
+        out.push(DIGITS[(d_lo + d_hi).div_ceil(2)]);
+    } else if let Some(hi) = hi.filter(|hi| hi.len() > 1) {
+        // `hi` is longer than one digit, so its first digit alone is already below it
+        out.push(hi[0]);
+    } else {
+        out.push(DIGITS[d_lo]);
+        midpoint(lo.get(1..).unwrap_or(&[]), None, out);
+    }
+}
+
+/// `n` evenly spaced keys of the shortest length that fits them, for rebalancing a bucket.
+pub fn spread(n: usize) -> Vec<String> {
+    let mut len = 1;
+    let mut space = BASE as u128;
+    while space <= n as u128 {
+        len += 1;
+        space *= BASE as u128;
+    }
+    (1..=n as u128)
+        .map(|i| {
+            let mut value = i * space / (n as u128 + 1);
+            let mut key = vec![DIGITS[0]; len];
+            for slot in key.iter_mut().rev() {
+                *slot = DIGITS[(value % BASE as u128) as usize];
+                value /= BASE as u128;
+            }
+            while key.last() == Some(&DIGITS[0]) {
+                key.pop();
+            }
+            String::from_utf8(key).unwrap_or_default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_between(lo: Option<&str>, hi: Option<&str>) -> String {
+        let key = between(lo, hi);
+        assert!(!key.is_empty(), "empty key between {lo:?} and {hi:?}");
+        assert!(!key.ends_with('0'), "{key} ends in the zero digit");
+        if let Some(lo) = lo {
+            assert!(lo < key.as_str(), "{key} is not after {lo}");
+        }
+        if let Some(hi) = hi {
+            assert!(key.as_str() < hi, "{key} is not before {hi}");
+        }
+        key
+    }
+
+    #[test]
+    fn between_is_strictly_inside() {
+        let cases = [
+            (None, None),
+            (Some("V"), None),
+            (None, Some("V")),
+            (Some("A"), Some("B")),
+            (Some("A"), Some("A1")),
+            (Some("Az"), Some("B")),
+            (Some("z"), None),
+            (Some("zzz"), None),
+            (None, Some("1")),
+            (None, Some("01")),
+            (Some("1"), Some("11")),
+            (Some("A01"), Some("A1")),
+        ];
+        for (lo, hi) in cases {
+            check_between(lo, hi);
+        }
+    }
+
+    #[test]
+    fn out_of_order_bounds_fall_back_to_after_lo() {
+        assert!(between(Some("B"), Some("A")).as_str() > "B");
+        assert!(between(Some("B"), Some("B")).as_str() > "B");
+    }
+
+    #[test]
+    fn repeated_inserts_at_the_back() {
+        let mut last: Option<String> = None;
+        for _ in 0..500 {
+            last = Some(check_between(last.as_deref(), None));
+        }
+    }
+
+    #[test]
+    fn repeated_inserts_at_the_front() {
+        let mut first: Option<String> = None;
+        for _ in 0..500 {
+            first = Some(check_between(None, first.as_deref()));
+        }
+    }
+
+    #[test]
+    fn repeated_inserts_into_the_same_gap() {
+        let (lo, mut hi) = ("A".to_string(), "B".to_string());
+        for _ in 0..200 {
+            hi = check_between(Some(&lo), Some(&hi));
+        }
+        // Each halving adds length, which is what the rebalance threshold catches
+        assert!(hi.len() > REBALANCE_KEY_LEN);
+    }
+
+    #[test]
+    fn spread_is_ordered_and_short() {
+        for n in [0, 1, 2, 61, 62, 63, 1000, 5000] {
+            let keys = spread(n);
+            assert_eq!(keys.len(), n);
+            assert!(keys.windows(2).all(|w| w[0] < w[1]), "spread({n}) is not strictly increasing");
+            assert!(keys.iter().all(|k| !k.is_empty() && !k.ends_with('0')));
+            // A freshly rebalanced bucket is well under the threshold
+            assert!(keys.iter().all(|k| k.len() < REBALANCE_KEY_LEN));
+        }
+    }
+
+    #[test]
+    fn spread_leaves_room_at_both_ends_and_between() {
+        let keys = spread(10);
+        check_between(None, Some(&keys[0]));
+        check_between(keys.last().map(String::as_str), None);
+        for w in keys.windows(2) {
+            check_between(Some(&w[0]), Some(&w[1]));
+        }
+    }
+}
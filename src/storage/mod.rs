@@ -0,0 +1,286 @@
+// Everything the server stores goes through `Storage`, and through the
+// `Transaction`s it hands out for changes that touch several rows. There is
+// one implementation per database, each with its own SQL and its own
+// migrations (migrations/sqlite, migrations/postgres); `database` in the
+// config picks one by its scheme: a file path or sqlite:// for SQLite,
+// postgres:// for Postgres.
+//
+// Methods return the domain types from main.rs, decoded straight from the
+// rows (`sqlx::FromRow` / `sqlx::Type`), so a value the code doesn't know is
+// a decode error rather than a guess. Timestamps are UTC, as stored.
+
+use std::{path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::{admin::ExportTask, config, Board, Bucket, Role, Task, TaskDraft, TaskType};
+
+pub mod postgres;
+pub mod sqlite;
+
+pub type Db = Arc<dyn Storage>;
+
+/// Connect to the configured database and bring its schema up to date.
+pub async fn connect(database: &config::Database) -> anyhow::Result<Db> {
+    let pool_size = config::get().pool_size;
+    Ok(match database {
+        config::Database::Sqlite(path) => Arc::new(sqlite::SqliteStorage::open(path, pool_size).await?),
+        config::Database::Postgres(url) => Arc::new(postgres::PostgresStorage::open(url, pool_size).await?),
+    })
+}
+
+/// A fresh database for tests: a scratch schema in $DATABASE_URL when it's set
+/// (Postgres), otherwise SQLite in memory.
+#[cfg(test)]
+pub async fn scratch() -> Db {
+    match std::env::var("DATABASE_URL") {
+        Ok(url) => Arc::new(postgres::PostgresStorage::scratch(&url).await),
+        Err(_) => Arc::new(sqlite::SqliteStorage::memory().await),
+    }
+}
+
+// Tags come back as one space-separated GROUP_CONCAT / string_agg column
+#[derive(sqlx::Type)]
+#[sqlx(transparent)]
+pub struct TagList(Option<String>);
+
+impl From<TagList> for Vec<String> {
+    fn from(TagList(s): TagList) -> Self {
+        let mut tags: Vec<String> = s.unwrap_or_default().split_whitespace().map(str::to_string).collect();
+        tags.sort();
+        tags
+    }
+}
+
+// Where a task sits, as far as moving it is concerned
+#[derive(sqlx::FromRow)]
+pub struct Placement {
+    pub board_id: i64,
+    pub bucket: Bucket,
+    pub task_type: TaskType,
+    pub assignee: Option<String>,
+    pub completed: bool,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    pub events: String, // comma-separated, or '*'
+    pub task_type: Option<TaskType>,
+}
+
+// A row of the delivery log
+#[derive(sqlx::FromRow)]
+pub struct Delivery {
+    pub event: String,
+    pub status: String,
+    pub attempts: i64,
+    pub response_code: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub url: String,
+}
+
+// A delivery the worker has claimed and should send now
+#[derive(sqlx::FromRow)]
+pub struct DueDelivery {
+    pub id: i64,
+    pub event: String,
+    pub payload: String,
+    pub url: String,
+    pub secret: String,
+    pub attempts: i64,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct IncomingToken {
+    pub id: i64,
+    pub label: String,
+    pub created_by: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Start a write transaction. Writers take turns (SQLite's write lock, a
+    /// Postgres advisory lock), so what a transaction reads stays true until
+    /// it commits.
+    async fn begin(&self) -> sqlx::Result<Box<dyn Transaction>>;
+
+    // --- Maintenance ---
+
+    /// Latest applied migration: version and description.
+    async fn schema_version(&self) -> sqlx::Result<(i64, String)>;
+    /// Consistent copy of the whole database in a new file.
+    async fn backup(&self, to: &Path) -> anyhow::Result<()>;
+    /// Reclaim unused space; returns the size in bytes before and after.
+    async fn vacuum(&self) -> anyhow::Result<(u64, u64)>;
+
+    // --- Boards and sharing ---
+
+    /// Boards `username` owns or is a member of, their own first.
+    async fn boards_for(&self, username: &str) -> sqlx::Result<Vec<Board>>;
+    /// (id, name, owner) of every board, or just `only`.
+    async fn all_boards(&self, only: Option<i64>) -> sqlx::Result<Vec<(i64, String, String)>>;
+    async fn board_role(&self, username: &str, board_id: i64) -> sqlx::Result<Option<Role>>;
+    /// The owner, then the members by name.
+    async fn board_usernames(&self, board_id: i64) -> sqlx::Result<Vec<String>>;
+    async fn members(&self, board_id: i64) -> sqlx::Result<Vec<(String, Role)>>;
+    /// Add a member, or change an existing member's role.
+    async fn set_member(&self, board_id: i64, username: &str, role: Role) -> sqlx::Result<()>;
+    async fn remove_member(&self, board_id: i64, username: &str) -> sqlx::Result<()>;
+
+    // --- Users (other than the configured admin) ---
+
+    async fn user_exists(&self, username: &str) -> sqlx::Result<bool>;
+    async fn password_hash(&self, username: &str) -> sqlx::Result<Option<String>>;
+    /// Usernames and when they were added.
+    async fn users(&self) -> sqlx::Result<Vec<(String, DateTime<Utc>)>>;
+    async fn create_user(&self, username: &str, password_hash: &str) -> sqlx::Result<()>;
+    async fn set_password(&self, username: &str, password_hash: &str) -> sqlx::Result<()>;
+
+    // --- Tasks ---
+
+    /// A board's tasks, ordered by bucket, then sort key.
+    async fn tasks(&self, board_id: i64) -> sqlx::Result<Vec<Task>>;
+    async fn task(&self, id: i64) -> sqlx::Result<Option<Task>>;
+    async fn task_board(&self, id: i64) -> sqlx::Result<Option<i64>>;
+    /// (id, title, completed at) of a board's completed tasks, newest first.
+    async fn completed_tasks(&self, board_id: i64, limit: i64) -> sqlx::Result<Vec<(i64, String, DateTime<Utc>)>>;
+    async fn toggle_completed(&self, id: i64, username: &str) -> sqlx::Result<()>;
+    /// The new version, or None when `expected` is given and doesn't match.
+    async fn rename_task(&self, id: i64, title: &str, username: &str, expected: Option<i64>) -> sqlx::Result<Option<i64>>;
+    async fn assign_task(&self, id: i64, assignee: Option<&str>, username: &str) -> sqlx::Result<()>;
+    /// Id and completed flag of the task carrying `external_id` on the board.
+    async fn external_task(&self, board_id: i64, external_id: &str) -> sqlx::Result<Option<(i64, bool)>>;
+    /// New title and (when given) notes; also reopens the task.
+    async fn refresh_external_task(&self, id: i64, title: &str, notes: Option<&str>, username: &str) -> sqlx::Result<()>;
+    /// A board's tasks with their tags, as written by `eisenpower-rs export`.
+    async fn export_tasks(&self, board_id: i64) -> sqlx::Result<Vec<ExportTask>>;
+    /// (board, bucket) pairs whose sort keys are longer than `max_key_len` or collide.
+    async fn unbalanced_buckets(&self, max_key_len: usize) -> sqlx::Result<Vec<(Option<i64>, String)>>;
+
+    // --- Email-in ---
+
+    /// The user's inbox secret, stored as `new_secret` if they have none yet.
+    async fn inbox_secret(&self, username: &str, new_secret: &str) -> sqlx::Result<String>;
+    async fn delete_inbox(&self, username: &str) -> sqlx::Result<()>;
+    async fn inbox_owner(&self, secret: &str) -> sqlx::Result<Option<String>>;
+
+    // --- Outgoing webhooks ---
+
+    async fn webhooks(&self, board_id: i64) -> sqlx::Result<Vec<Webhook>>;
+    async fn create_webhook(
+        &self,
+        board_id: i64,
+        url: &str,
+        events: &str,
+        task_type: Option<TaskType>,
+        secret: &str,
+        created_by: &str,
+    ) -> sqlx::Result<()>;
+    async fn delete_webhook(&self, board_id: i64, id: i64) -> sqlx::Result<()>;
+    async fn recent_deliveries(&self, board_id: i64, limit: i64) -> sqlx::Result<Vec<Delivery>>;
+    /// One pending delivery per webhook on the task's board that wants `event`; returns how many.
+    async fn queue_deliveries(&self, task: &Task, event: &str, payload: &str) -> sqlx::Result<u64>;
+    /// Pending deliveries due at `now`, oldest first. Claimed ones aren't
+    /// handed out again for `lease_secs`, so several servers can share the queue.
+    async fn claim_deliveries(&self, limit: i64, lease_secs: i64, now: DateTime<Utc>) -> sqlx::Result<Vec<DueDelivery>>;
+    async fn delivery_succeeded(&self, id: i64, attempts: i64, response_code: Option<i64>, now: DateTime<Utc>) -> sqlx::Result<()>;
+    /// Record a failed attempt: retried at `retry_at`, or failed for good when that is None.
+    async fn delivery_failed(
+        &self,
+        id: i64,
+        attempts: i64,
+        response_code: Option<i64>,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> sqlx::Result<()>;
+
+    // --- Incoming tokens ---
+
+    async fn incoming_tokens(&self, board_id: i64) -> sqlx::Result<Vec<IncomingToken>>;
+    async fn create_incoming_token(&self, board_id: i64, label: &str, token_hash: &str, created_by: &str) -> sqlx::Result<()>;
+    async fn delete_incoming_token(&self, board_id: i64, id: i64) -> sqlx::Result<()>;
+    /// (id, board, creator) of the token with this SHA-256.
+    async fn incoming_token(&self, token_hash: &str) -> sqlx::Result<Option<(i64, i64, String)>>;
+    async fn touch_incoming_token(&self, id: i64) -> sqlx::Result<()>;
+}
+
+/// Changes that have to land together. Dropping one without `commit` rolls it back.
+#[async_trait]
+pub trait Transaction: Send {
+    async fn commit(self: Box<Self>) -> sqlx::Result<()>;
+
+    // --- Boards ---
+
+    async fn create_board(&mut self, name: &str, owner: &str) -> sqlx::Result<i64>;
+    /// Move tasks from before boards existed onto `board_id`.
+    async fn adopt_orphan_tasks(&mut self, board_id: i64) -> sqlx::Result<()>;
+    async fn add_member(&mut self, board_id: i64, username: &str, role: Role) -> sqlx::Result<()>;
+    /// Removes the board with its tasks and members.
+    async fn delete_board(&mut self, id: i64) -> sqlx::Result<()>;
+
+    // --- Tasks ---
+
+    async fn insert_task(
+        &mut self,
+        board_id: i64,
+        username: &str,
+        draft: &TaskDraft,
+        sort_key: &str,
+        assignee: Option<&str>,
+    ) -> sqlx::Result<i64>;
+    /// A task from an export, keeping its authors and timestamps.
+    async fn import_task(&mut self, board_id: i64, task: &ExportTask) -> sqlx::Result<i64>;
+    async fn delete_task(&mut self, id: i64) -> sqlx::Result<bool>;
+    async fn placement(&mut self, id: i64) -> sqlx::Result<Option<Placement>>;
+    async fn add_tag(&mut self, task_id: i64, tag: &str) -> sqlx::Result<()>;
+    async fn remove_tag(&mut self, task_id: i64, tag: &str) -> sqlx::Result<()>;
+    async fn set_completed(&mut self, id: i64, completed: bool, username: &str) -> sqlx::Result<()>;
+    /// Bump the version, e.g. after changing tags.
+    async fn touch_task(&mut self, id: i64, username: &str) -> sqlx::Result<()>;
+
+    // The moves below check `expected` against the task's version when it's
+    // given, and return false when it doesn't match.
+
+    /// New quadrant for a task staying where it is (someone else's Today list).
+    async fn reclassify(&mut self, id: i64, task_type: TaskType, username: &str, expected: Option<i64>) -> sqlx::Result<bool>;
+    /// Into a quadrant, which also becomes its type.
+    async fn move_to_quadrant(&mut self, id: i64, bucket: Bucket, sort_key: &str, username: &str, expected: Option<i64>) -> sqlx::Result<bool>;
+    /// Onto `username`'s Today list.
+    async fn move_to_today(&mut self, id: i64, sort_key: &str, username: &str, expected: Option<i64>) -> sqlx::Result<bool>;
+    /// Onto another board, landing in `bucket` and assigned to `assignee`.
+    async fn move_to_board(
+        &mut self,
+        id: i64,
+        board_id: i64,
+        bucket: Bucket,
+        assignee: Option<&str>,
+        sort_key: &str,
+        username: &str,
+    ) -> sqlx::Result<bool>;
+
+    // --- Sort keys ---
+
+    /// Open tasks shown in `column` to `viewer`, in order, each flagged
+    /// whether it really sits in that bucket (quadrants also show others'
+    /// Today tasks of their type), with its sort key.
+    async fn column(&mut self, board_id: i64, column: Bucket, viewer: &str) -> sqlx::Result<Vec<(i64, bool, String)>>;
+    async fn last_sort_key(&mut self, board_id: i64, bucket: Bucket) -> sqlx::Result<Option<String>>;
+    /// Key of task `id` if it's in the bucket and isn't `except`.
+    async fn sort_key_of(&mut self, id: i64, board_id: i64, bucket: Bucket, except: i64) -> sqlx::Result<Option<String>>;
+    /// Nearest key after `key` in the bucket, ignoring `except`.
+    async fn sort_key_after(&mut self, board_id: i64, bucket: Bucket, except: i64, key: &str) -> sqlx::Result<Option<String>>;
+    /// Nearest key before `key` in the bucket, ignoring `except`.
+    async fn sort_key_before(&mut self, board_id: i64, bucket: Bucket, except: i64, key: &str) -> sqlx::Result<Option<String>>;
+    /// New key for a task still in `bucket`. A reorder isn't an edit, so the
+    /// version and updated_at/updated_by stay as they are.
+    async fn set_sort_key(&mut self, id: i64, bucket: Bucket, sort_key: &str) -> sqlx::Result<()>;
+    /// Ids in a bucket in key order, for rebalancing.
+    async fn bucket_ids(&mut self, board_id: Option<i64>, bucket: &str) -> sqlx::Result<Vec<i64>>;
+    /// Replace a key without touching anything else about the task.
+    async fn rewrite_sort_key(&mut self, id: i64, sort_key: &str) -> sqlx::Result<()>;
+}
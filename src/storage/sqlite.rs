@@ -0,0 +1,872 @@
+// SQLite backend: a single database file, migrations in migrations/sqlite.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    ConnectOptions, Sqlite, SqlitePool,
+};
+
+use super::{Delivery, DueDelivery, IncomingToken, Placement, Storage, Transaction, Webhook};
+use crate::{
+    admin::ExportTask, Board, Bucket, Role, Task, TaskDraft, TaskType,
+};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+pub struct SqliteStorage {
+    pool: SqlitePool,
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    /// Pool for the database file, created if missing and migrated to the latest schema.
+    pub async fn open(path: &Path, pool_size: u32) -> anyhow::Result<SqliteStorage> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(pool_size)
+            .connect_with(SqliteConnectOptions::new().filename(path).create_if_missing(true))
+            .await
+            .with_context(|| format!("could not open {}", path.display()))?;
+        MIGRATOR.run(&pool).await?;
+        Ok(SqliteStorage { pool, path: path.to_path_buf() })
+    }
+
+    pub async fn close(self) {
+        self.pool.close().await;
+    }
+
+    /// A fresh migrated database that lives as long as its one connection, for tests.
+    #[cfg(test)]
+    pub async fn memory() -> SqliteStorage {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(SqliteConnectOptions::new().in_memory(true))
+            .await
+            .unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+        SqliteStorage { pool, path: PathBuf::new() }
+    }
+
+    #[cfg(test)]
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+/// Highest migration applied to the database file at `path`, opened read-only.
+pub async fn file_schema_version(path: &Path) -> anyhow::Result<i64> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await
+        .with_context(|| format!("could not open {}", path.display()))?;
+    let version: Option<i64> = sqlx::query_scalar(r#"SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1"#)
+        .fetch_one(&mut conn)
+        .await
+        .with_context(|| format!("{} is not an eisenpower database", path.display()))?;
+    Ok(version.unwrap_or(0))
+}
+
+const TASK_COLUMNS: &str = r#"id, title, task_type, bucket, completed, sort_key, board_id, created_by, updated_by, assignee, due_date, notes, external_id, version, created_at, updated_at,
+                  (SELECT GROUP_CONCAT(tag, ' ') FROM task_tags WHERE task_id = tasks.id) AS tags"#;
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn begin(&self) -> sqlx::Result<Box<dyn Transaction>> {
+        // IMMEDIATE takes the write lock up front so reads inside can't go stale before the writes
+        Ok(Box::new(SqliteTransaction(self.pool.begin_with("BEGIN IMMEDIATE").await?)))
+    }
+
+    async fn schema_version(&self) -> sqlx::Result<(i64, String)> {
+        sqlx::query_as(r#"SELECT version, description FROM _sqlx_migrations ORDER BY version DESC LIMIT 1"#)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    async fn backup(&self, to: &Path) -> anyhow::Result<()> {
+        if to.exists() {
+            bail!("{} already exists", to.display());
+        }
+        sqlx::query("VACUUM INTO ?1")
+            .bind(to.to_string_lossy().as_ref())
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("could not write {}", to.display()))?;
+        Ok(())
+    }
+
+    async fn vacuum(&self) -> anyhow::Result<(u64, u64)> {
+        // Fold the write-ahead log into the file first so the sizes compare like for like
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&self.pool).await?;
+        let before = file_size(&self.path);
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&self.pool).await?;
+        sqlx::query("PRAGMA optimize").execute(&self.pool).await?;
+        Ok((before, file_size(&self.path)))
+    }
+
+    async fn boards_for(&self, username: &str) -> sqlx::Result<Vec<Board>> {
+        sqlx::query_as(
+            r#"SELECT b.id, b.name, b.owner,
+                      CASE WHEN b.owner = ?1 THEN 'owner' ELSE m.role END AS role
+               FROM boards b
+               LEFT JOIN board_members m ON m.board_id = b.id AND m.username = ?1
+               WHERE b.owner = ?1 OR m.username IS NOT NULL
+               ORDER BY b.owner = ?1 DESC, b.id"#)
+            .bind(username)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn all_boards(&self, only: Option<i64>) -> sqlx::Result<Vec<(i64, String, String)>> {
+        sqlx::query_as(r#"SELECT id, name, owner FROM boards WHERE ?1 IS NULL OR id = ?1 ORDER BY id"#)
+            .bind(only)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn board_role(&self, username: &str, board_id: i64) -> sqlx::Result<Option<Role>> {
+        let row: Option<(Option<Role>,)> = sqlx::query_as(
+            r#"SELECT CASE WHEN b.owner = ?2 THEN 'owner' ELSE m.role END
+               FROM boards b
+               LEFT JOIN board_members m ON m.board_id = b.id AND m.username = ?2
+               WHERE b.id = ?1"#,
+        )
+        .bind(board_id)
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.and_then(|(role,)| role))
+    }
+
+    async fn board_usernames(&self, board_id: i64) -> sqlx::Result<Vec<String>> {
+        sqlx::query_scalar(
+            r#"SELECT owner AS username FROM boards WHERE id = ?1
+               UNION ALL
+               SELECT username FROM (SELECT username FROM board_members WHERE board_id = ?1 ORDER BY username)"#,
+        )
+        .bind(board_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn members(&self, board_id: i64) -> sqlx::Result<Vec<(String, Role)>> {
+        sqlx::query_as(r#"SELECT username, role FROM board_members WHERE board_id = ?1 ORDER BY username"#)
+            .bind(board_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn set_member(&self, board_id: i64, username: &str, role: Role) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"INSERT INTO board_members(board_id, username, role) VALUES (?1, ?2, ?3)
+               ON CONFLICT(board_id, username) DO UPDATE SET role = excluded.role"#,
+        )
+        .bind(board_id)
+        .bind(username)
+        .bind(role.as_str())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_member(&self, board_id: i64, username: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"DELETE FROM board_members WHERE board_id = ?1 AND username = ?2"#)
+            .bind(board_id)
+            .bind(username)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn user_exists(&self, username: &str) -> sqlx::Result<bool> {
+        let row = sqlx::query(r#"SELECT id FROM users WHERE username = ?1"#)
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn password_hash(&self, username: &str) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar(r#"SELECT password_hash FROM users WHERE username = ?1"#)
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn users(&self) -> sqlx::Result<Vec<(String, DateTime<Utc>)>> {
+        sqlx::query_as(r#"SELECT username, created_at FROM users ORDER BY username"#)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn create_user(&self, username: &str, password_hash: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"INSERT INTO users(username, password_hash) VALUES (?1, ?2)"#)
+            .bind(username)
+            .bind(password_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_password(&self, username: &str, password_hash: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"UPDATE users SET password_hash = ?2 WHERE username = ?1"#)
+            .bind(username)
+            .bind(password_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn tasks(&self, board_id: i64) -> sqlx::Result<Vec<Task>> {
+        sqlx::query_as(&format!(
+            r#"SELECT {TASK_COLUMNS}
+               FROM tasks
+               WHERE board_id = ?1
+               ORDER BY bucket, sort_key, id"#
+        ))
+        .bind(board_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn task(&self, id: i64) -> sqlx::Result<Option<Task>> {
+        sqlx::query_as(&format!(r#"SELECT {TASK_COLUMNS} FROM tasks WHERE id = ?1"#))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn task_board(&self, id: i64) -> sqlx::Result<Option<i64>> {
+        sqlx::query_scalar(r#"SELECT board_id FROM tasks WHERE id = ?1"#)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn completed_tasks(&self, board_id: i64, limit: i64) -> sqlx::Result<Vec<(i64, String, DateTime<Utc>)>> {
+        sqlx::query_as(
+            r#"SELECT id, title, updated_at FROM tasks
+               WHERE completed = 1 AND board_id = ?1
+               ORDER BY updated_at DESC LIMIT ?2"#,
+        )
+        .bind(board_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn toggle_completed(&self, id: i64, username: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"UPDATE tasks
+               SET completed = 1 - completed, version = version + 1, updated_at = datetime('now'), updated_by = ?2
+               WHERE id = ?1"#,
+        )
+        .bind(id)
+        .bind(username)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn rename_task(&self, id: i64, title: &str, username: &str, expected: Option<i64>) -> sqlx::Result<Option<i64>> {
+        sqlx::query_scalar(
+            r#"UPDATE tasks SET title = ?1, version = version + 1, updated_at = datetime('now'), updated_by = ?3
+               WHERE id = ?2 AND (?4 IS NULL OR version = ?4)
+               RETURNING version"#,
+        )
+        .bind(title)
+        .bind(id)
+        .bind(username)
+        .bind(expected)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn assign_task(&self, id: i64, assignee: Option<&str>, username: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"UPDATE tasks SET assignee = ?1, version = version + 1, updated_at = datetime('now'), updated_by = ?3 WHERE id = ?2"#,
+        )
+        .bind(assignee)
+        .bind(id)
+        .bind(username)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn external_task(&self, board_id: i64, external_id: &str) -> sqlx::Result<Option<(i64, bool)>> {
+        sqlx::query_as(r#"SELECT id, completed FROM tasks WHERE board_id = ?1 AND external_id = ?2"#)
+            .bind(board_id)
+            .bind(external_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn refresh_external_task(&self, id: i64, title: &str, notes: Option<&str>, username: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"UPDATE tasks
+               SET title = ?2, notes = COALESCE(?3, notes), completed = 0,
+                   version = version + 1, updated_at = datetime('now'), updated_by = ?4
+               WHERE id = ?1"#,
+        )
+        .bind(id)
+        .bind(title)
+        .bind(notes)
+        .bind(username)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn export_tasks(&self, board_id: i64) -> sqlx::Result<Vec<ExportTask>> {
+        let mut tasks: Vec<ExportTask> = sqlx::query_as(
+            r#"SELECT id, title, task_type, bucket, completed, sort_key, created_by, updated_by, assignee,
+                      due_date, notes, external_id, created_at, updated_at
+               FROM tasks WHERE board_id = ?1 ORDER BY bucket, sort_key, id"#,
+        )
+        .bind(board_id)
+        .fetch_all(&self.pool)
+        .await?;
+        for task in &mut tasks {
+            task.tags = sqlx::query_scalar(r#"SELECT tag FROM task_tags WHERE task_id = ?1 ORDER BY tag"#)
+                .bind(task.id)
+                .fetch_all(&self.pool)
+                .await?;
+        }
+        Ok(tasks)
+    }
+
+    async fn unbalanced_buckets(&self, max_key_len: usize) -> sqlx::Result<Vec<(Option<i64>, String)>> {
+        sqlx::query_as(
+            r#"SELECT board_id, bucket FROM tasks
+               GROUP BY board_id, bucket
+               HAVING MAX(LENGTH(sort_key)) > ?1 OR COUNT(DISTINCT sort_key) < COUNT(*)"#,
+        )
+        .bind(max_key_len as i64)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn inbox_secret(&self, username: &str, new_secret: &str) -> sqlx::Result<String> {
+        sqlx::query(r#"INSERT OR IGNORE INTO mail_inboxes(username, secret) VALUES (?1, ?2)"#)
+            .bind(username)
+            .bind(new_secret)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query_scalar(r#"SELECT secret FROM mail_inboxes WHERE username = ?1"#)
+            .bind(username)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    async fn delete_inbox(&self, username: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"DELETE FROM mail_inboxes WHERE username = ?1"#)
+            .bind(username)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn inbox_owner(&self, secret: &str) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar(r#"SELECT username FROM mail_inboxes WHERE secret = ?1"#)
+            .bind(secret)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn webhooks(&self, board_id: i64) -> sqlx::Result<Vec<Webhook>> {
+        sqlx::query_as(r#"SELECT id, url, events, task_type FROM webhooks WHERE board_id = ?1 ORDER BY id"#)
+            .bind(board_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn create_webhook(
+        &self,
+        board_id: i64,
+        url: &str,
+        events: &str,
+        task_type: Option<TaskType>,
+        secret: &str,
+        created_by: &str,
+    ) -> sqlx::Result<()> {
+        sqlx::query(r#"INSERT INTO webhooks(board_id, url, events, task_type, secret, created_by) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"#)
+            .bind(board_id)
+            .bind(url)
+            .bind(events)
+            .bind(task_type.map(|t| t.as_str()))
+            .bind(secret)
+            .bind(created_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_webhook(&self, board_id: i64, id: i64) -> sqlx::Result<()> {
+        sqlx::query(r#"DELETE FROM webhooks WHERE id = ?1 AND board_id = ?2"#)
+            .bind(id)
+            .bind(board_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn recent_deliveries(&self, board_id: i64, limit: i64) -> sqlx::Result<Vec<Delivery>> {
+        sqlx::query_as(
+            r#"SELECT d.event, d.status, d.attempts, d.response_code, d.last_error, d.created_at, w.url
+               FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+               WHERE w.board_id = ?1
+               ORDER BY d.id DESC LIMIT ?2"#,
+        )
+        .bind(board_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn queue_deliveries(&self, task: &Task, event: &str, payload: &str) -> sqlx::Result<u64> {
+        let res = sqlx::query(
+            r#"INSERT INTO webhook_deliveries(webhook_id, event, payload)
+               SELECT id, ?2, ?3 FROM webhooks
+               WHERE board_id = ?1
+                 AND (events = '*' OR ',' || events || ',' LIKE '%,' || ?2 || ',%')
+                 AND (task_type IS NULL OR task_type = ?4)"#,
+        )
+        .bind(task.board_id)
+        .bind(event)
+        .bind(payload)
+        .bind(task.task_type.as_str())
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn claim_deliveries(&self, limit: i64, lease_secs: i64, now: DateTime<Utc>) -> sqlx::Result<Vec<DueDelivery>> {
+        // datetime() puts the bound timestamp in the same text form as the stored ones
+        let mut due: Vec<DueDelivery> = sqlx::query_as(
+            r#"UPDATE webhook_deliveries
+               SET next_attempt_at = datetime(?3, '+' || ?2 || ' seconds')
+               WHERE id IN (SELECT id FROM webhook_deliveries
+                            WHERE status = 'pending' AND next_attempt_at <= datetime(?3)
+                            ORDER BY id LIMIT ?1)
+               RETURNING id, event, payload, attempts,
+                         (SELECT url FROM webhooks WHERE id = webhook_id) AS url,
+                         (SELECT secret FROM webhooks WHERE id = webhook_id) AS secret"#,
+        )
+        .bind(limit)
+        .bind(lease_secs)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        due.sort_by_key(|d| d.id);
+        Ok(due)
+    }
+
+    async fn delivery_succeeded(&self, id: i64, attempts: i64, response_code: Option<i64>, now: DateTime<Utc>) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"UPDATE webhook_deliveries
+               SET status = 'delivered', attempts = ?2, response_code = ?3, last_error = NULL, delivered_at = datetime(?4)
+               WHERE id = ?1"#,
+        )
+        .bind(id)
+        .bind(attempts)
+        .bind(response_code)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delivery_failed(
+        &self,
+        id: i64,
+        attempts: i64,
+        response_code: Option<i64>,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"UPDATE webhook_deliveries
+               SET status = ?2, attempts = ?3, response_code = ?4, last_error = ?5,
+                   next_attempt_at = COALESCE(datetime(?6), next_attempt_at)
+               WHERE id = ?1"#,
+        )
+        .bind(id)
+        .bind(if retry_at.is_some() { "pending" } else { "failed" })
+        .bind(attempts)
+        .bind(response_code)
+        .bind(error)
+        .bind(retry_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn incoming_tokens(&self, board_id: i64) -> sqlx::Result<Vec<IncomingToken>> {
+        sqlx::query_as(r#"SELECT id, label, created_by, last_used_at FROM incoming_tokens WHERE board_id = ?1 ORDER BY id"#)
+            .bind(board_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn create_incoming_token(&self, board_id: i64, label: &str, token_hash: &str, created_by: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"INSERT INTO incoming_tokens(board_id, label, token_hash, created_by) VALUES (?1, ?2, ?3, ?4)"#)
+            .bind(board_id)
+            .bind(label)
+            .bind(token_hash)
+            .bind(created_by)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_incoming_token(&self, board_id: i64, id: i64) -> sqlx::Result<()> {
+        sqlx::query(r#"DELETE FROM incoming_tokens WHERE id = ?1 AND board_id = ?2"#)
+            .bind(id)
+            .bind(board_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn incoming_token(&self, token_hash: &str) -> sqlx::Result<Option<(i64, i64, String)>> {
+        sqlx::query_as(r#"SELECT id, board_id, created_by FROM incoming_tokens WHERE token_hash = ?1"#)
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn touch_incoming_token(&self, id: i64) -> sqlx::Result<()> {
+        sqlx::query(r#"UPDATE incoming_tokens SET last_used_at = datetime('now') WHERE id = ?1"#)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct SqliteTransaction(sqlx::Transaction<'static, Sqlite>);
+
+#[async_trait]
+impl Transaction for SqliteTransaction {
+    async fn commit(self: Box<Self>) -> sqlx::Result<()> {
+        self.0.commit().await
+    }
+
+    async fn create_board(&mut self, name: &str, owner: &str) -> sqlx::Result<i64> {
+        Ok(sqlx::query(r#"INSERT INTO boards(name, owner) VALUES (?1, ?2)"#)
+            .bind(name)
+            .bind(owner)
+            .execute(&mut *self.0)
+            .await?
+            .last_insert_rowid())
+    }
+
+    async fn adopt_orphan_tasks(&mut self, board_id: i64) -> sqlx::Result<()> {
+        sqlx::query(r#"UPDATE tasks SET board_id = ?1 WHERE board_id IS NULL"#)
+            .bind(board_id)
+            .execute(&mut *self.0)
+            .await?;
+        Ok(())
+    }
+
+    async fn add_member(&mut self, board_id: i64, username: &str, role: Role) -> sqlx::Result<()> {
+        sqlx::query(r#"INSERT INTO board_members(board_id, username, role) VALUES (?1, ?2, ?3)"#)
+            .bind(board_id)
+            .bind(username)
+            .bind(role.as_str())
+            .execute(&mut *self.0)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_board(&mut self, id: i64) -> sqlx::Result<()> {
+        sqlx::query(r#"DELETE FROM tasks WHERE board_id = ?1"#)
+            .bind(id)
+            .execute(&mut *self.0)
+            .await?;
+        sqlx::query(r#"DELETE FROM board_members WHERE board_id = ?1"#)
+            .bind(id)
+            .execute(&mut *self.0)
+            .await?;
+        sqlx::query(r#"DELETE FROM boards WHERE id = ?1"#)
+            .bind(id)
+            .execute(&mut *self.0)
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_task(
+        &mut self,
+        board_id: i64,
+        username: &str,
+        draft: &TaskDraft,
+        sort_key: &str,
+        assignee: Option<&str>,
+    ) -> sqlx::Result<i64> {
+        Ok(sqlx::query(
+            r#"INSERT INTO tasks(title, task_type, bucket, sort_key, board_id, created_by, updated_by, assignee, due_date, notes, external_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?7, ?8, ?9, ?10)"#,
+        )
+        .bind(&draft.title)
+        .bind(draft.task_type.as_str())
+        .bind(draft.bucket.as_str())
+        .bind(sort_key)
+        .bind(board_id)
+        .bind(username)
+        .bind(assignee)
+        .bind(draft.due_date)
+        .bind(&draft.notes)
+        .bind(&draft.external_id)
+        .execute(&mut *self.0)
+        .await?
+        .last_insert_rowid())
+    }
+
+    async fn import_task(&mut self, board_id: i64, t: &ExportTask) -> sqlx::Result<i64> {
+        sqlx::query_scalar(
+            r#"INSERT INTO tasks(title, task_type, bucket, completed, sort_key, board_id, created_by, updated_by,
+                                 assignee, due_date, notes, external_id, created_at, updated_at)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+               RETURNING id"#,
+        )
+        .bind(&t.title)
+        .bind(&t.task_type)
+        .bind(&t.bucket)
+        .bind(t.completed)
+        .bind(&t.sort_key)
+        .bind(board_id)
+        .bind(&t.created_by)
+        .bind(&t.updated_by)
+        .bind(&t.assignee)
+        .bind(&t.due_date)
+        .bind(&t.notes)
+        .bind(&t.external_id)
+        .bind(&t.created_at)
+        .bind(&t.updated_at)
+        .fetch_one(&mut *self.0)
+        .await
+    }
+
+    async fn delete_task(&mut self, id: i64) -> sqlx::Result<bool> {
+        let res = sqlx::query(r#"DELETE FROM tasks WHERE id = ?1"#)
+            .bind(id)
+            .execute(&mut *self.0)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn placement(&mut self, id: i64) -> sqlx::Result<Option<Placement>> {
+        sqlx::query_as(r#"SELECT board_id, bucket, task_type, assignee, completed FROM tasks WHERE id = ?1"#)
+            .bind(id)
+            .fetch_optional(&mut *self.0)
+            .await
+    }
+
+    async fn add_tag(&mut self, task_id: i64, tag: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"INSERT OR IGNORE INTO task_tags(task_id, tag) VALUES (?1, ?2)"#)
+            .bind(task_id)
+            .bind(tag)
+            .execute(&mut *self.0)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_tag(&mut self, task_id: i64, tag: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"DELETE FROM task_tags WHERE task_id = ?1 AND tag = ?2"#)
+            .bind(task_id)
+            .bind(tag)
+            .execute(&mut *self.0)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_completed(&mut self, id: i64, completed: bool, username: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            r#"UPDATE tasks SET completed = ?1, version = version + 1, updated_at = datetime('now'), updated_by = ?3 WHERE id = ?2"#,
+        )
+        .bind(completed)
+        .bind(id)
+        .bind(username)
+        .execute(&mut *self.0)
+        .await?;
+        Ok(())
+    }
+
+    async fn touch_task(&mut self, id: i64, username: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"UPDATE tasks SET version = version + 1, updated_at = datetime('now'), updated_by = ?2 WHERE id = ?1"#)
+            .bind(id)
+            .bind(username)
+            .execute(&mut *self.0)
+            .await?;
+        Ok(())
+    }
+
+    async fn reclassify(&mut self, id: i64, task_type: TaskType, username: &str, expected: Option<i64>) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            r#"UPDATE tasks SET task_type = ?1, version = version + 1, updated_at = datetime('now'), updated_by = ?3
+               WHERE id = ?2 AND (?4 IS NULL OR version = ?4)"#,
+        )
+        .bind(task_type.as_str())
+        .bind(id)
+        .bind(username)
+        .bind(expected)
+        .execute(&mut *self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn move_to_quadrant(&mut self, id: i64, bucket: Bucket, sort_key: &str, username: &str, expected: Option<i64>) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            r#"UPDATE tasks SET bucket = ?1, task_type = ?2, sort_key = ?3, version = version + 1, updated_at = datetime('now'), updated_by = ?5
+               WHERE id = ?4 AND (?6 IS NULL OR version = ?6)"#,
+        )
+        .bind(bucket.as_str())
+        .bind(TaskType::from_bucket(bucket).as_str())
+        .bind(sort_key)
+        .bind(id)
+        .bind(username)
+        .bind(expected)
+        .execute(&mut *self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn move_to_today(&mut self, id: i64, sort_key: &str, username: &str, expected: Option<i64>) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            r#"UPDATE tasks SET bucket = ?1, sort_key = ?2, assignee = ?4, version = version + 1, updated_at = datetime('now'), updated_by = ?4
+               WHERE id = ?3 AND (?5 IS NULL OR version = ?5)"#,
+        )
+        .bind(Bucket::Today.as_str())
+        .bind(sort_key)
+        .bind(id)
+        .bind(username)
+        .bind(expected)
+        .execute(&mut *self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn move_to_board(
+        &mut self,
+        id: i64,
+        board_id: i64,
+        bucket: Bucket,
+        assignee: Option<&str>,
+        sort_key: &str,
+        username: &str,
+    ) -> sqlx::Result<bool> {
+        let res = sqlx::query(
+            r#"UPDATE tasks
+               SET board_id = ?1,
+                   sort_key = ?4,
+                   bucket = ?5,
+                   assignee = ?6,
+                   version = version + 1,
+                   updated_at = datetime('now'),
+                   updated_by = ?3
+               WHERE id = ?2"#,
+        )
+        .bind(board_id)
+        .bind(id)
+        .bind(username)
+        .bind(sort_key)
+        .bind(bucket.as_str())
+        .bind(assignee)
+        .execute(&mut *self.0)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn column(&mut self, board_id: i64, column: Bucket, viewer: &str) -> sqlx::Result<Vec<(i64, bool, String)>> {
+        sqlx::query_as(
+            r#"SELECT id, bucket = ?2 AND NOT (bucket = 'Today' AND COALESCE(assignee, ?3) != ?3) AS own, sort_key
+               FROM tasks
+               WHERE board_id = ?1 AND completed = 0
+                 AND ((bucket = ?2 AND NOT (bucket = 'Today' AND COALESCE(assignee, ?3) != ?3))
+                      OR (bucket = 'Today' AND task_type = ?2 AND COALESCE(assignee, ?3) != ?3))
+               ORDER BY sort_key, id"#,
+        )
+        .bind(board_id)
+        .bind(column.as_str())
+        .bind(viewer)
+        .fetch_all(&mut *self.0)
+        .await
+    }
+
+    async fn last_sort_key(&mut self, board_id: i64, bucket: Bucket) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar(r#"SELECT MAX(sort_key) FROM tasks WHERE board_id = ?1 AND bucket = ?2"#)
+            .bind(board_id)
+            .bind(bucket.as_str())
+            .fetch_one(&mut *self.0)
+            .await
+    }
+
+    async fn sort_key_of(&mut self, id: i64, board_id: i64, bucket: Bucket, except: i64) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar(r#"SELECT sort_key FROM tasks WHERE id = ?1 AND board_id = ?2 AND bucket = ?3 AND id != ?4"#)
+            .bind(id)
+            .bind(board_id)
+            .bind(bucket.as_str())
+            .bind(except)
+            .fetch_optional(&mut *self.0)
+            .await
+    }
+
+    async fn sort_key_after(&mut self, board_id: i64, bucket: Bucket, except: i64, key: &str) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar(r#"SELECT MIN(sort_key) FROM tasks WHERE board_id = ?1 AND bucket = ?2 AND id != ?3 AND sort_key > ?4"#)
+            .bind(board_id)
+            .bind(bucket.as_str())
+            .bind(except)
+            .bind(key)
+            .fetch_one(&mut *self.0)
+            .await
+    }
+
+    async fn sort_key_before(&mut self, board_id: i64, bucket: Bucket, except: i64, key: &str) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar(r#"SELECT MAX(sort_key) FROM tasks WHERE board_id = ?1 AND bucket = ?2 AND id != ?3 AND sort_key < ?4"#)
+            .bind(board_id)
+            .bind(bucket.as_str())
+            .bind(except)
+            .bind(key)
+            .fetch_one(&mut *self.0)
+            .await
+    }
+
+    async fn set_sort_key(&mut self, id: i64, bucket: Bucket, sort_key: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"UPDATE tasks SET sort_key = ?1 WHERE id = ?2 AND bucket = ?3"#)
+            .bind(sort_key)
+            .bind(id)
+            .bind(bucket.as_str())
+            .execute(&mut *self.0)
+            .await?;
+        Ok(())
+    }
+
+    async fn bucket_ids(&mut self, board_id: Option<i64>, bucket: &str) -> sqlx::Result<Vec<i64>> {
+        sqlx::query_scalar(r#"SELECT id FROM tasks WHERE board_id IS ?1 AND bucket = ?2 ORDER BY sort_key, id"#)
+            .bind(board_id)
+            .bind(bucket)
+            .fetch_all(&mut *self.0)
+            .await
+    }
+
+    async fn rewrite_sort_key(&mut self, id: i64, sort_key: &str) -> sqlx::Result<()> {
+        sqlx::query(r#"UPDATE tasks SET sort_key = ?1 WHERE id = ?2"#)
+            .bind(sort_key)
+            .bind(id)
+            .execute(&mut *self.0)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+// Brute-force protection for basic auth. Failed logins are counted per client
+// address and per username. The first few are free; after that each failure
+// doubles how long that address or name has to wait before trying again, and
+// a long run of them locks the address out for a while. A name only ever gets
+// the backoff: anyone can fail logins as someone else, and a lockout on the
+// name alone would keep its owner out. A success clears both counts.
+// Every failure and lockout is logged under the `audit` target.
+//
+// Behind a reverse proxy every client shares the proxy's address, so the
+// per-address limit then applies to all of them together.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::{info, warn};
+
+const FREE_ATTEMPTS: u32 = 3;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+const LOCKOUT_AFTER: u32 = 10;
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60); // since the last failure
+const MAX_TRACKED: usize = 10_000;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Ip(IpAddr),
+    User(String),
+}
+
+struct Record {
+    failures: u32,
+    last: Instant,
+    blocked_until: Instant,
+}
+
+#[derive(Default)]
+pub struct Throttle {
+    records: Mutex<HashMap<Key, Record>>,
+}
+
+// Whole seconds, rounded up, for Retry-After and the log
+pub fn secs(d: Duration) -> u64 {
+    d.as_secs() + u64::from(d.subsec_nanos() > 0)
+}
+
+fn shown(ip: Option<IpAddr>) -> String {
+    ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string())
+}
+
+fn keys(ip: Option<IpAddr>, username: &str) -> impl Iterator<Item = Key> {
+    ip.map(Key::Ip).into_iter().chain([Key::User(username.to_string())])
+}
+
+// Wait imposed after the `failures`th failure in a row
+fn delay(failures: u32) -> Duration {
+    if failures >= LOCKOUT_AFTER {
+        LOCKOUT
+    } else if failures > FREE_ATTEMPTS {
+        BASE_DELAY.saturating_mul(1 << (failures - FREE_ATTEMPTS - 1)).min(MAX_DELAY)
+    } else {
+        Duration::ZERO
+    }
+}
+
+impl Throttle {
+    // How much longer the caller has to wait, when its address or the username is held back
+    pub fn blocked(&self, ip: Option<IpAddr>, username: &str) -> Option<Duration> {
+        self.blocked_at(ip, username, Instant::now())
+    }
+
+    pub fn failed(&self, ip: Option<IpAddr>, username: &str) {
+        self.failed_at(ip, username, Instant::now())
+    }
+
+    pub fn succeeded(&self, ip: Option<IpAddr>, username: &str) {
+        let mut records = self.records.lock().unwrap();
+        for key in keys(ip, username) {
+            records.remove(&key);
+        }
+    }
+
+    fn blocked_at(&self, ip: Option<IpAddr>, username: &str, now: Instant) -> Option<Duration> {
+        let records = self.records.lock().unwrap();
+        let wait = keys(ip, username)
+            .filter_map(|k| records.get(&k))
+            .map(|r| r.blocked_until.saturating_duration_since(now))
+            .max()
+            .filter(|w| !w.is_zero())?;
+        info!(target: "audit", ip = shown(ip), user = username, wait_secs = secs(wait), "login refused while held back");
+        Some(wait)
+    }
+
+    fn failed_at(&self, ip: Option<IpAddr>, username: &str, now: Instant) {
+        let mut records = self.records.lock().unwrap();
+        if records.len() >= MAX_TRACKED {
+            records.retain(|_, r| now.duration_since(r.last) < FORGET_AFTER || r.blocked_until > now);
+        }
+        if records.len() >= MAX_TRACKED {
+            // Still full, of recent failures from many names or addresses: forget
+            // the tenth that failed longest ago rather than grow without bound
+            let mut oldest: Vec<(Instant, Key)> = records.iter().map(|(k, r)| (r.last, k.clone())).collect();
+            oldest.sort_unstable_by_key(|(last, _)| *last);
+            for (_, key) in oldest.into_iter().take(records.len() - MAX_TRACKED * 9 / 10) {
+                records.remove(&key);
+            }
+        }
+        let mut failures = 0;
+        let mut locked_out = false;
+        for key in keys(ip, username) {
+            let name_only = matches!(key, Key::User(_));
+            let r = records.entry(key).or_insert(Record { failures: 0, last: now, blocked_until: now });
+            if now.duration_since(r.last) >= FORGET_AFTER {
+                r.failures = 0;
+            }
+            r.failures += 1;
+            r.last = now;
+            let wait = if name_only { delay(r.failures).min(MAX_DELAY) } else { delay(r.failures) };
+            r.blocked_until = now + wait;
+            failures = failures.max(r.failures);
+            locked_out |= !name_only && r.failures == LOCKOUT_AFTER;
+        }
+        warn!(target: "audit", ip = shown(ip), user = username, failures, "login failed");
+        if locked_out {
+            warn!(target: "audit", ip = shown(ip), user = username, minutes = LOCKOUT.as_secs() / 60, "login locked out");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1)));
+    const OTHER_IP: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2)));
+    const S: Duration = Duration::from_secs(1);
+
+    fn fail(t: &Throttle, ip: Option<IpAddr>, username: &str, times: u32, now: Instant) {
+        for _ in 0..times {
+            t.failed_at(ip, username, now);
+        }
+    }
+
+    #[test]
+    fn delay_schedule() {
+        let waits: Vec<u64> = (1..=LOCKOUT_AFTER).map(|n| delay(n).as_secs()).collect();
+        assert_eq!(waits, [0, 0, 0, 1, 2, 4, 8, 16, 32, 15 * 60]);
+        assert_eq!(delay(LOCKOUT_AFTER + 5), LOCKOUT);
+        assert!((FREE_ATTEMPTS + 1..LOCKOUT_AFTER).all(|n| delay(n) <= MAX_DELAY));
+    }
+
+    #[test]
+    fn free_attempts_then_backoff() {
+        let t = Throttle::default();
+        let now = Instant::now();
+        fail(&t, IP, "alice", FREE_ATTEMPTS, now);
+        assert_eq!(t.blocked_at(IP, "alice", now), None);
+        t.failed_at(IP, "alice", now);
+        assert_eq!(t.blocked_at(IP, "alice", now), Some(S));
+        assert_eq!(t.blocked_at(IP, "alice", now + S / 2), Some(S / 2));
+        assert_eq!(t.blocked_at(IP, "alice", now + S), None);
+        t.failed_at(IP, "alice", now + S);
+        assert_eq!(t.blocked_at(IP, "alice", now + S), Some(2 * S));
+    }
+
+    #[test]
+    fn locks_out_after_ten_failures() {
+        let t = Throttle::default();
+        let now = Instant::now();
+        fail(&t, IP, "alice", LOCKOUT_AFTER - 1, now);
+        assert_eq!(t.blocked_at(IP, "alice", now), Some(32 * S));
+        t.failed_at(IP, "alice", now);
+        assert_eq!(t.blocked_at(IP, "alice", now), Some(LOCKOUT));
+        assert_eq!(t.blocked_at(IP, "alice", now + LOCKOUT - S), Some(S));
+        assert_eq!(t.blocked_at(IP, "alice", now + LOCKOUT), None);
+    }
+
+    #[test]
+    fn address_and_username_are_counted_separately() {
+        let t = Throttle::default();
+        let now = Instant::now();
+        fail(&t, IP, "alice", FREE_ATTEMPTS + 1, now);
+        // The same name from elsewhere, and another name from the same address
+        assert_eq!(t.blocked_at(OTHER_IP, "alice", now), Some(S));
+        assert_eq!(t.blocked_at(IP, "bob", now), Some(S));
+        assert_eq!(t.blocked_at(OTHER_IP, "bob", now), None);
+        assert_eq!(t.blocked_at(None, "alice", now), Some(S));
+    }
+
+    #[test]
+    fn success_forgets_both() {
+        let t = Throttle::default();
+        let now = Instant::now();
+        fail(&t, IP, "alice", FREE_ATTEMPTS + 2, now);
+        t.succeeded(IP, "alice");
+        assert_eq!(t.blocked_at(IP, "alice", now), None);
+        // Counting starts over
+        fail(&t, IP, "alice", FREE_ATTEMPTS, now);
+        assert_eq!(t.blocked_at(IP, "alice", now), None);
+    }
+
+    #[test]
+    fn failures_are_forgotten_after_an_hour() {
+        let t = Throttle::default();
+        let now = Instant::now();
+        fail(&t, IP, "alice", LOCKOUT_AFTER - 1, now);
+        let later = now + FORGET_AFTER;
+        t.failed_at(IP, "alice", later);
+        assert_eq!(t.blocked_at(IP, "alice", later), None);
+    }
+
+    #[test]
+    fn old_records_are_pruned_when_full() {
+        let t = Throttle::default();
+        let now = Instant::now();
+        for i in 0..MAX_TRACKED {
+            t.failed_at(None, &format!("user{i}"), now);
+        }
+        t.failed_at(None, "late", now + FORGET_AFTER);
+        assert_eq!(t.records.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn the_oldest_records_are_evicted_when_full_of_recent_ones() {
+        let t = Throttle::default();
+        let now = Instant::now();
+        let ms = Duration::from_millis(1);
+        for i in 0..MAX_TRACKED as u32 {
+            t.failed_at(None, &format!("user{i}"), now + i * ms);
+        }
+        let later = now + MAX_TRACKED as u32 * ms;
+        fail(&t, IP, "late", FREE_ATTEMPTS + 1, later);
+        let records = t.records.lock().unwrap();
+        assert!(records.len() <= MAX_TRACKED);
+        assert!(!records.contains_key(&Key::User("user0".to_string())));
+        assert!(records.contains_key(&Key::User(format!("user{}", MAX_TRACKED - 1))));
+        drop(records);
+        assert_eq!(t.blocked_at(IP, "late", later), Some(S));
+    }
+
+    #[test]
+    fn a_lockout_stays_with_the_address() {
+        let t = Throttle::default();
+        let now = Instant::now();
+        fail(&t, IP, "admin", LOCKOUT_AFTER + 5, now);
+        assert_eq!(t.blocked_at(IP, "admin", now), Some(LOCKOUT));
+        // The name itself only waits out the backoff
+        assert_eq!(t.blocked_at(OTHER_IP, "admin", now), Some(MAX_DELAY));
+        let later = now + MAX_DELAY;
+        assert_eq!(t.blocked_at(OTHER_IP, "admin", later), None);
+        assert!(t.blocked_at(IP, "admin", later).is_some());
+        t.succeeded(OTHER_IP, "admin");
+        assert_eq!(t.blocked_at(OTHER_IP, "admin", later), None);
+        assert_eq!(t.blocked_at(IP, "bob", later), Some(LOCKOUT - MAX_DELAY));
+    }
+}
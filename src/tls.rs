@@ -0,0 +1,102 @@
+// HTTPS for the web server. The certificate and key are read from the PEM files
+// in the [tls] settings and read again whenever either file changes, so a
+// renewed certificate is picked up without a restart. The optional redirect
+// listener answers plain HTTP with a redirect to the same URL over HTTPS.
+
+use std::{net::SocketAddr, path::Path, time::{Duration, SystemTime}};
+
+use anyhow::Context;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use tracing::{info, warn};
+
+use crate::config;
+
+const CHECK_EVERY: Duration = Duration::from_secs(30);
+
+pub async fn load(tls: &config::Tls) -> anyhow::Result<RustlsConfig> {
+    // reqwest links rustls with ring too; whichever installs it first wins
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+        .await
+        .with_context(|| format!("could not load TLS certificate {} and key {}", tls.cert.display(), tls.key.display()))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Reload when either file's modification time moves. A failed reload (say the
+// certificate is written before its key) keeps the old pair and is retried on
+// the next check.
+pub async fn watch(rustls: RustlsConfig, tls: &'static config::Tls) {
+    let mut loaded = (modified(&tls.cert), modified(&tls.key));
+    let mut interval = tokio::time::interval(CHECK_EVERY);
+    loop {
+        interval.tick().await;
+        let now = (modified(&tls.cert), modified(&tls.key));
+        if now == loaded {
+            continue;
+        }
+        match rustls.reload_from_pem_file(&tls.cert, &tls.key).await {
+            Ok(()) => {
+                info!(cert = %tls.cert.display(), "reloaded TLS certificate");
+                loaded = now;
+            }
+            Err(e) => warn!(error = %e, cert = %tls.cert.display(), "could not reload TLS certificate; keeping the old one"),
+        }
+    }
+}
+
+pub async fn redirect(addr: SocketAddr, https_port: u16) -> anyhow::Result<()> {
+    let app = Router::new().fallback(to_https).with_state(https_port);
+    let listener = tokio::net::TcpListener::bind(addr).await.with_context(|| format!("could not listen on {addr}"))?;
+    info!(?addr, "redirecting http to https");
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn to_https(State(port): State<u16>, headers: HeaderMap, uri: Uri) -> Response {
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok()).or_else(|| uri.authority().map(|a| a.as_str()));
+    let Some(host) = host else {
+        return (StatusCode::BAD_REQUEST, "Host header required").into_response();
+    };
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    Redirect::permanent(&https_url(host, port, path)).into_response()
+}
+
+// The same host on the HTTPS port: the port the request came in on is dropped
+// (keeping [..] around IPv6 addresses) and 443 is left implicit
+fn https_url(host: &str, port: u16, path: &str) -> String {
+    let host = match host.rsplit_once(':') {
+        Some((name, p)) if !p.contains(']') && p.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    let port = if port == 443 { String::new() } else { format!(":{port}") };
+    format!("https://{host}{port}{path}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::https_url;
+
+    #[test]
+    fn keeps_host_and_path_and_swaps_the_port() {
+        assert_eq!(https_url("example.com:80", 443, "/a?b=1"), "https://example.com/a?b=1");
+        assert_eq!(https_url("example.com", 443, "/"), "https://example.com/");
+        assert_eq!(https_url("example.com:8080", 8443, "/x"), "https://example.com:8443/x");
+        assert_eq!(https_url("127.0.0.1:8098", 8443, "/tasks"), "https://127.0.0.1:8443/tasks");
+    }
+
+    #[test]
+    fn ipv6_hosts_keep_their_brackets() {
+        assert_eq!(https_url("[::1]:8098", 8443, "/"), "https://[::1]:8443/");
+        assert_eq!(https_url("[::1]", 443, "/"), "https://[::1]/");
+        assert_eq!(https_url("[2001:db8::1]", 8443, "/"), "https://[2001:db8::1]:8443/");
+    }
+}
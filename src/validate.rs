@@ -0,0 +1,152 @@
+// Checks on what clients send, kept in one place so every route applies the
+// same limits. A failure is a 422 whose message starts with the field name.
+
+use std::{collections::HashSet, fmt::Display};
+
+use crate::{error::AppError, parse_bucket, parse_quadrant, Bucket};
+
+pub const MAX_TITLE_CHARS: usize = 500;
+pub const MAX_NOTES_CHARS: usize = 10_000;
+pub const MAX_NAME_CHARS: usize = 100; // board names, usernames, token labels, external ids
+pub const MAX_IDS: usize = 1000; // tasks in one reorder, move or bulk request
+
+fn invalid(field: &str, problem: impl Display) -> AppError {
+    AppError::invalid(format!("{field} {problem}"))
+}
+
+// Trimmed single line of text: not blank, no control characters, at most `max` characters
+pub fn line(field: &str, s: &str, max: usize) -> Result<String, AppError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(invalid(field, "is required"));
+    }
+    if s.chars().count() > max {
+        return Err(invalid(field, format!("is longer than {max} characters")));
+    }
+    if s.chars().any(char::is_control) {
+        return Err(invalid(field, "must be a single line without control characters"));
+    }
+    Ok(s.to_string())
+}
+
+pub fn title(field: &str, s: &str) -> Result<String, AppError> {
+    line(field, s, MAX_TITLE_CHARS)
+}
+
+// Optional multi-line text; blank means none
+pub fn notes(field: &str, s: Option<&str>) -> Result<Option<String>, AppError> {
+    let Some(s) = s.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(None);
+    };
+    if s.chars().count() > MAX_NOTES_CHARS {
+        return Err(invalid(field, format!("is longer than {MAX_NOTES_CHARS} characters")));
+    }
+    if s.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        return Err(invalid(field, "must not contain control characters"));
+    }
+    Ok(Some(s.to_string()))
+}
+
+pub fn bucket(field: &str, s: &str) -> Result<Bucket, AppError> {
+    parse_bucket(s).ok_or_else(|| {
+        invalid(
+            field,
+            format!("must be one of UrgentImportant, UrgentNotImportant, NotUrgentImportant, NotUrgentNotImportant or Today, not {s:?}"),
+        )
+    })
+}
+
+// A Bucket name or its short form (ui, uni, nui, nun, today)
+pub fn quadrant(field: &str, s: &str) -> Result<Bucket, AppError> {
+    parse_quadrant(s.trim()).ok_or_else(|| invalid(field, format!("must be ui, uni, nui, nun or today, not {s:?}")))
+}
+
+pub fn id(field: &str, id: i64) -> Result<i64, AppError> {
+    if id <= 0 {
+        return Err(invalid(field, format!("must be a positive task id, not {id}")));
+    }
+    Ok(id)
+}
+
+// Task ids: positive, each listed once, at most MAX_IDS of them
+pub fn ids(field: &str, ids: &[i64]) -> Result<(), AppError> {
+    if ids.len() > MAX_IDS {
+        return Err(invalid(field, format!("lists {} tasks; at most {MAX_IDS} are allowed", ids.len())));
+    }
+    let mut seen = HashSet::new();
+    for &i in ids {
+        if i <= 0 {
+            return Err(invalid(field, format!("holds {i}, which is not a task id")));
+        }
+        if !seen.insert(i) {
+            return Err(invalid(field, format!("lists task {i} more than once")));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message<T>(r: Result<T, AppError>) -> String {
+        match r {
+            Ok(_) => panic!("expected a validation error"),
+            Err(e) => e.message(),
+        }
+    }
+
+    #[test]
+    fn title_length_is_counted_in_characters() {
+        assert_eq!(title("title", &"a".repeat(MAX_TITLE_CHARS)).unwrap().len(), MAX_TITLE_CHARS);
+        assert_eq!(title("title", &"é".repeat(MAX_TITLE_CHARS)).unwrap().chars().count(), MAX_TITLE_CHARS);
+        assert_eq!(message(title("title", &"a".repeat(MAX_TITLE_CHARS + 1))), "title is longer than 500 characters");
+    }
+
+    #[test]
+    fn lines_are_trimmed_and_single() {
+        assert_eq!(title("title", "  Buy milk \n").unwrap(), "Buy milk");
+        assert_eq!(message(title("title", " \t ")), "title is required");
+        assert_eq!(message(line("name", "a\nb", MAX_NAME_CHARS)), "name must be a single line without control characters");
+        assert!(line("name", "a\u{7}", MAX_NAME_CHARS).is_err());
+    }
+
+    #[test]
+    fn notes_allow_newlines_and_blank_means_none() {
+        assert_eq!(notes("notes", None).unwrap(), None);
+        assert_eq!(notes("notes", Some("  \n ")).unwrap(), None);
+        assert_eq!(notes("notes", Some("a\r\n\tb")).unwrap().as_deref(), Some("a\r\n\tb"));
+        assert!(notes("notes", Some("a\u{0}b")).is_err());
+        assert!(notes("notes", Some(&"a".repeat(MAX_NOTES_CHARS + 1))).is_err());
+    }
+
+    #[test]
+    fn enum_fields() {
+        assert!(matches!(bucket("bucket", "Today").unwrap(), Bucket::Today));
+        assert!(matches!(bucket("bucket", "NotUrgentImportant").unwrap(), Bucket::NotUrgentImportant));
+        assert!(message(bucket("bucket", "today")).starts_with("bucket must be one of UrgentImportant"));
+        assert!(matches!(quadrant("quadrant", " nui ").unwrap(), Bucket::NotUrgentImportant));
+        assert!(matches!(quadrant("quadrant", "today").unwrap(), Bucket::Today));
+        assert_eq!(message(quadrant("quadrant", "x")), "quadrant must be ui, uni, nui, nun or today, not \"x\"");
+    }
+
+    #[test]
+    fn single_ids_are_positive() {
+        assert_eq!(id("id", 1).unwrap(), 1);
+        assert_eq!(message(id("prevId", 0)), "prevId must be a positive task id, not 0");
+        assert!(id("id", -5).is_err());
+    }
+
+    #[test]
+    fn id_lists() {
+        // An empty list is an empty column
+        assert!(ids("orderedIds", &[]).is_ok());
+        assert!(ids("ids", &[3, 1, 2]).is_ok());
+        assert_eq!(message(ids("ids", &[1, 2, 1])), "ids lists task 1 more than once");
+        assert_eq!(message(ids("ids", &[1, 0])), "ids holds 0, which is not a task id");
+        let many: Vec<i64> = (1..=MAX_IDS as i64).collect();
+        assert!(ids("ids", &many).is_ok());
+        let too_many: Vec<i64> = (1..=MAX_IDS as i64 + 1).collect();
+        assert_eq!(message(ids("ids", &too_many)), "ids lists 1001 tasks; at most 1000 are allowed");
+    }
+}
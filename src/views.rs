@@ -0,0 +1,156 @@
+// Pages and htmx fragments, rendered from templates/*.html. Askama checks the
+// templates at compile time and HTML-escapes everything they interpolate, so
+// nothing here builds markup by hand.
+
+use askama::Template;
+use axum::response::Html;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    config, error::AppResult, quick_add::QuickAdd, storage::{Delivery, IncomingToken, Webhook}, Board, Bucket,
+    Role, Task, TaskType,
+};
+
+pub fn html(t: &impl Template) -> AppResult<Html<String>> {
+    Ok(Html(t.render()?))
+}
+
+// One quadrant (or Today) on the board page
+pub struct Column {
+    pub bucket: Bucket,
+    pub class: &'static str,
+    pub badge: &'static str,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Template)]
+#[template(path = "index.html")]
+pub struct Index<'a> {
+    pub boards: &'a [Board],
+    pub current: &'a Board,
+    pub columns: Vec<Column>,
+    pub read_only: bool,
+    pub is_admin: bool,
+    pub csrf_token: String, // sent back on every mutation, see csrf.rs
+    pub database: Option<String>, // admin only; the password in a Postgres URL is redacted
+    pub mail_enabled: bool,
+    pub webhooks_enabled: bool, // outgoing or incoming, either gets the Webhooks panel
+}
+
+// A single card, for handlers that answer with the task they changed
+#[derive(Template)]
+#[template(path = "task.html")]
+pub struct TaskCard<'a> {
+    pub task: &'a Task,
+}
+
+#[derive(Template)]
+#[template(path = "preview.html")]
+pub struct Preview {
+    pub q: QuickAdd,
+}
+
+#[derive(Template)]
+#[template(path = "inbox.html")]
+pub struct Inbox<'a> {
+    pub domain: Option<&'a str>, // None when email-in is off
+    pub secret: String,
+}
+
+#[derive(Template)]
+#[template(path = "webhooks.html")]
+pub struct Webhooks<'a> {
+    pub board_id: i64,
+    pub hooks: Option<Vec<Webhook>>, // None when outgoing webhooks are off
+    pub events: String,
+    pub deliveries: Vec<Delivery>,
+    pub tokens: Option<Vec<IncomingToken>>, // None when incoming webhooks are off
+    pub new_token: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "board_picker.html")]
+pub struct BoardPicker {
+    pub task_id: i64,
+    pub boards: Vec<Board>,
+}
+
+#[derive(Template)]
+#[template(path = "assignee_picker.html")]
+pub struct AssigneePicker {
+    pub task_id: i64,
+    pub current: Option<String>,
+    pub names: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "members.html")]
+pub struct Members {
+    pub board_id: i64,
+    pub members: Vec<(String, Role)>,
+}
+
+#[derive(Template)]
+#[template(path = "users.html")]
+pub struct Users {
+    pub admin: String,
+    pub users: Vec<(String, DateTime<Utc>)>,
+}
+
+#[derive(Template)]
+#[template(path = "completed.html")]
+pub struct Completed {
+    pub tasks: Vec<(i64, String, DateTime<Utc>)>,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+pub struct ErrorFragment<'a> {
+    pub message: &'a str,
+}
+
+// Helpers the task card calls
+impl Task {
+    // Colour chip; a Today card keeps the colour of its quadrant
+    fn chip_class(&self) -> &'static str {
+        let task_type = match self.bucket {
+            Bucket::Today => self.task_type,
+            _ => TaskType::from_bucket(self.bucket),
+        };
+        match task_type {
+            TaskType::UrgentImportant => "color-UI",
+            TaskType::UrgentNotImportant => "color-UNI",
+            TaskType::NotUrgentImportant => "color-NUI",
+            TaskType::NotUrgentNotImportant => "color-NUN",
+        }
+    }
+
+    fn overdue(&self) -> bool {
+        !self.completed && self.due_date.is_some_and(|due| due < config::today())
+    }
+
+    // Last editor for the "edited by" line, when not the author
+    fn editor(&self) -> Option<&str> {
+        self.updated_by.as_deref().filter(|u| self.created_by.as_deref() != Some(*u))
+    }
+}
+
+// Up to two initials for an avatar: "alice" -> "AL", "alice.smith" -> "AS"
+fn initials(username: &str) -> String {
+    let parts: Vec<&str> = username
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let letters: String = if parts.len() >= 2 {
+        parts.iter().take(2).filter_map(|p| p.chars().next()).collect()
+    } else {
+        username.chars().filter(|c| c.is_alphanumeric()).take(2).collect()
+    };
+    letters.to_uppercase()
+}
+
+// Stable per-user avatar colour so the same person looks the same on every card
+fn avatar_color(username: &str) -> String {
+    let hue = username.bytes().fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32)) % 360;
+    format!("hsl({}, 45%, 40%)", hue)
+}
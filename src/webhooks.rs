@@ -0,0 +1,366 @@
+// Outgoing webhooks for task events.
+//
+// Handlers call `emit` after a change is stored. That records one pending
+// delivery per matching subscription and wakes the worker, which POSTs the
+// JSON payload signed with the subscription's secret:
+//
+//   X-Eisenpower-Signature: sha256=<hex HMAC-SHA256 of the body>
+//
+// Failed deliveries are retried with exponential backoff and give up after
+// MAX_ATTEMPTS; every attempt's outcome is kept for the delivery log. A pass
+// claims its deliveries for CLAIM_LEASE_SECS first, so servers sharing one
+// database don't send the same delivery twice.
+//
+// Any board owner can add a webhook, so deliveries only go to public
+// addresses (plus the admin's webhooks.allow list): the URL is checked when
+// the webhook is added and again on every connection, in case the name now
+// resolves somewhere else. Redirects aren't followed.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::{
+    storage::{Db, DueDelivery, Storage},
+    Task,
+};
+
+pub const EVENTS: [&str; 5] = ["task.created", "task.completed", "task.reopened", "task.moved", "task.deleted"];
+
+const MAX_ATTEMPTS: i64 = 6;
+const FIRST_RETRY_SECS: i64 = 30;
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+// Longer than a pass can take: 50 deliveries at the client's 10s timeout
+const CLAIM_LEASE_SECS: i64 = 10 * 60;
+
+static WAKE: Notify = Notify::const_new();
+
+/// Queue `event` for every webhook on the task's board that wants it.
+pub async fn emit(db: &dyn Storage, event: &str, task: &Task) {
+    // Nothing would ever deliver them
+    if !crate::config::get().features.webhooks {
+        return;
+    }
+    let payload = json!({
+        "event": event,
+        "occurred_at": chrono::Utc::now(),
+        "board_id": task.board_id,
+        "task": task,
+    })
+    .to_string();
+    match db.queue_deliveries(task, event, &payload).await {
+        Ok(queued) if queued > 0 => WAKE.notify_one(),
+        Ok(_) => {}
+        Err(e) => warn!(error = %e, event, "could not queue webhook deliveries"),
+    }
+}
+
+/// Deliver pending webhooks until the process exits.
+pub async fn run(db: Db) {
+    let targets = Targets::configured();
+    let client = targets.client();
+    loop {
+        if let Err(e) = deliver_due(&*db, &client, &targets, Utc::now()).await {
+            warn!(error = %e, "webhook delivery pass failed");
+        }
+        tokio::select! {
+            _ = WAKE.notified() => {}
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+}
+
+async fn deliver_due(db: &dyn Storage, client: &reqwest::Client, targets: &Targets, now: DateTime<Utc>) -> anyhow::Result<()> {
+    let due = db.claim_deliveries(50, CLAIM_LEASE_SECS, now).await?;
+    for DueDelivery { id, event, payload, url, secret, attempts } in due {
+        let attempts = attempts + 1;
+        // The client's resolver checks names, but an IP address in the URL never reaches it
+        if let Err(why) = targets.check(&url).await {
+            db.delivery_failed(id, attempts, None, &why, retry_at(attempts, now)).await?;
+            continue;
+        }
+        let outcome = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("X-Eisenpower-Event", &event)
+            .header("X-Eisenpower-Delivery", id.to_string())
+            .header("X-Eisenpower-Signature", signature(&secret, payload.as_bytes()))
+            .body(payload)
+            .send()
+            .await;
+        let (code, error) = match outcome {
+            Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16() as i64), None),
+            Ok(resp) => (Some(resp.status().as_u16() as i64), Some(format!("HTTP {}", resp.status()))),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        match error {
+            None => db.delivery_succeeded(id, attempts, code, now).await?,
+            Some(error) => db.delivery_failed(id, attempts, code, &error, retry_at(attempts, now)).await?,
+        }
+    }
+    Ok(())
+}
+
+/// Where deliveries may connect: public addresses and any the admin allowed.
+#[derive(Clone)]
+pub struct Targets {
+    allow: Arc<Vec<IpAddr>>,
+}
+
+impl Targets {
+    pub fn new(allow: Vec<IpAddr>) -> Targets {
+        Targets { allow: Arc::new(allow) }
+    }
+
+    pub fn configured() -> Targets {
+        Targets::new(crate::config::get().webhooks.allow.clone())
+    }
+
+    /// Err, saying why, unless `url` is http(s) to a host that only resolves to permitted addresses.
+    pub async fn check(&self, url: &str) -> Result<(), String> {
+        let url = reqwest::Url::parse(url).map_err(|_| "not a valid URL".to_string())?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err("URL must start with http:// or https://".to_string());
+        }
+        let host = url.host_str().ok_or_else(|| "URL has no host".to_string())?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        self.lookup(host, port).await.map(|_| ())
+    }
+
+    // An IPv6 host may come bracketed, as in a URL
+    async fn lookup(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("could not resolve {host}: {e}"))?
+            .collect();
+        match addrs.iter().find(|a| !self.permits(a.ip())) {
+            Some(a) => Err(format!("{host} is {}, which is not a public address", a.ip())),
+            None if addrs.is_empty() => Err(format!("could not resolve {host}")),
+            None => Ok(addrs),
+        }
+    }
+
+    fn permits(&self, ip: IpAddr) -> bool {
+        self.allow.contains(&ip) || is_public(ip)
+    }
+
+    /// A client that only connects to permitted addresses and doesn't follow redirects.
+    pub fn client(&self) -> reqwest::Client {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(self.clone()))
+            .build()
+            .expect("http client")
+    }
+}
+
+impl reqwest::dns::Resolve for Targets {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let targets = self.clone();
+        Box::pin(async move {
+            let addrs = targets.lookup(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+// Not loopback, private, link-local, shared (CGNAT), documentation or otherwise reserved
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || a >= 240
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (18..20).contains(&b)))
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let s = ip.segments();
+    // NAT64 (64:ff9b::/96) reaches the IPv4 address in the last 32 bits
+    if s[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [.., a, b, c, d] = ip.octets();
+        return is_public_v4(Ipv4Addr::new(a, b, c, d));
+    }
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (s[0] & 0xfe00) == 0xfc00 // unique local
+        || (s[0] & 0xffc0) == 0xfe80 // link-local
+        || (s[0] == 0x2001 && s[1] == 0x0db8)) // documentation
+}
+
+// When to try again after the `attempts`th failure: 30s, 1m, 2m, 4m, 8m, then
+// None: MAX_ATTEMPTS is the last one
+fn retry_at(attempts: i64, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    (attempts < MAX_ATTEMPTS).then(|| now + chrono::Duration::seconds(FIRST_RETRY_SECS << (attempts - 1).clamp(0, 10)))
+}
+
+/// Hex HMAC-SHA256 of `body`, as sent in X-Eisenpower-Signature.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// The X-Eisenpower-Signature header value
+fn signature(secret: &str, body: &[u8]) -> String {
+    format!("sha256={}", sign(secret, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{body::Bytes, http::HeaderMap, response::Redirect, routing::post, Router};
+
+    use super::*;
+    use crate::{Bucket, TaskDraft};
+
+    #[test]
+    fn signature_is_hex_hmac_sha256_of_the_body() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_ne!(sign("Jefe", b"what do ya want for nothing!"), sign("Jefe", b"what do ya want for nothing?"));
+    }
+
+    #[test]
+    fn retries_double_from_thirty_seconds_then_give_up() {
+        let now = Utc::now();
+        let waits: Vec<Option<i64>> = (1..=MAX_ATTEMPTS).map(|n| retry_at(n, now).map(|at| (at - now).num_seconds())).collect();
+        assert_eq!(waits, [Some(30), Some(60), Some(120), Some(240), Some(480), None]);
+    }
+
+    // A receiver that records what it's sent and always answers 500
+    async fn failing_receiver() -> (String, Arc<Mutex<Vec<(HeaderMap, Bytes)>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: Bytes| async move {
+                log.lock().unwrap().push((headers, body));
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, received)
+    }
+
+    async fn one_pending_delivery(url: &str) -> (Db, i64) {
+        crate::config::set_for_tests();
+        let db = crate::storage::scratch().await;
+        let board = crate::user_boards(&*db, "admin").await.unwrap()[0].id;
+        db.create_webhook(board, url, "*", None, "s3cret", "admin").await.unwrap();
+        crate::create_task(&*db, board, "admin", TaskDraft::new("ship it".to_string(), Bucket::UrgentImportant)).await.unwrap();
+        (db, board)
+    }
+
+    #[test]
+    fn only_public_addresses_are_public() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "255.255.255.255", "::1", "::", "fe80::1", "fd00::1", "::ffff:127.0.0.1", "64:ff9b::a00:1"] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["93.184.215.14", "1.1.1.1", "2606:4700:4700::1111", "64:ff9b::101:101"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn loopback_is_refused_unless_allowed() {
+        let (url, received) = failing_receiver().await;
+        let (db, board) = one_pending_delivery(&url).await;
+        let targets = Targets::new(Vec::new());
+        assert!(targets.check(&url).await.unwrap_err().contains("not a public address"));
+        assert!(targets.check("http://localhost/").await.is_err());
+
+        deliver_due(&*db, &targets.client(), &targets, Utc::now()).await.unwrap();
+        assert!(received.lock().unwrap().is_empty());
+        let log = db.recent_deliveries(board, 25).await.unwrap();
+        assert_eq!((log[0].attempts, log[0].response_code), (1, None));
+        assert!(log[0].last_error.as_deref().unwrap().contains("not a public address"), "{:?}", log[0].last_error);
+
+        // Nor reached through a public-looking name by the client itself
+        let err = targets.client().get(url.replace("127.0.0.1", "localhost")).send().await.unwrap_err();
+        assert!(err.is_connect(), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn redirects_are_not_followed() {
+        let (target, received) = failing_receiver().await;
+        let app = Router::new().route("/hook", post(move || async move { Redirect::temporary(&target) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let (db, board) = one_pending_delivery(&url).await;
+
+        let targets = Targets::new(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        deliver_due(&*db, &targets.client(), &targets, Utc::now()).await.unwrap();
+        assert!(received.lock().unwrap().is_empty());
+        let log = db.recent_deliveries(board, 25).await.unwrap();
+        assert_eq!((log[0].status.as_str(), log[0].response_code), ("pending", Some(307)));
+    }
+
+    #[tokio::test]
+    async fn signed_deliveries_back_off_and_give_up_after_six_attempts() {
+        let (url, received) = failing_receiver().await;
+        let (db, board) = one_pending_delivery(&url).await;
+        // The receiver is on loopback, so it has to be allowed
+        let targets = Targets::new(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        let client = targets.client();
+        let sent = || received.lock().unwrap().len();
+
+        let mut now = Utc::now();
+        deliver_due(&*db, &client, &targets, now).await.unwrap();
+        assert_eq!(sent(), 1);
+        for wait in [30, 60, 120, 240, 480] {
+            // Not due a second early
+            deliver_due(&*db, &client, &targets, now + chrono::Duration::seconds(wait - 1)).await.unwrap();
+            now += chrono::Duration::seconds(wait);
+            deliver_due(&*db, &client, &targets, now).await.unwrap();
+        }
+        assert_eq!(sent(), 6);
+        deliver_due(&*db, &client, &targets, now + chrono::Duration::days(1)).await.unwrap();
+        assert_eq!(sent(), 6);
+
+        let log = db.recent_deliveries(board, 25).await.unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!((log[0].status.as_str(), log[0].attempts, log[0].response_code), ("failed", 6, Some(500)));
+
+        for (headers, body) in received.lock().unwrap().iter() {
+            assert_eq!(headers["x-eisenpower-event"], "task.created");
+            assert_eq!(headers["x-eisenpower-signature"].to_str().unwrap(), signature("s3cret", body));
+        }
+    }
+}
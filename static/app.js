@@ -0,0 +1,379 @@
+// Board page behaviour: drag and drop, inline edits, panels, multi-select and
+// keyboard triage. READ_ONLY is set by the page before this loads.
+const CSRF_TOKEN = document.querySelector('meta[name="csrf-token"]').content;
+function bootSortable(listId, bucket){
+    const el = document.getElementById(listId);
+    if(!el || READ_ONLY) return;
+    new Sortable(el, {
+        animation: 150,
+        group: 'matrix',
+        delay: 350,
+        delayOnTouchOnly: true,
+        onEnd: function(evt){
+            if(!evt.item || !evt.to) return;
+            if(evt.to === evt.from && evt.newIndex === evt.oldIndex) return;
+            sendMove(evt.item, evt.to, evt.from, Number(evt.item.dataset.version));
+}
+});
+}
+function listIds(list){
+    return Array.from(list.querySelectorAll('li.task')).map(li => Number(li.dataset.id));
+}
+// One request carries the whole drop; the server applies it atomically
+function sendMove(item, to, from, version){
+    const newBucket = to.dataset.bucket;
+    return fetch('/move', {
+        method:'POST',
+        headers:{'Content-Type':'application/json', 'X-CSRF-Token': CSRF_TOKEN},
+        body: JSON.stringify({
+            id: Number(item.dataset.id),
+            bucket: newBucket,
+            orderedIds: listIds(to),
+            sourceIds: from !== to ? listIds(from) : null,
+            version: version
+        })
+    })
+    .then(response => {
+        if (response.status === 412) {
+            // Someone changed the task since this page loaded: offer to apply the move on top of their change
+            return response.json().then(server => {
+                if (confirm('"' + server.title + '" was changed elsewhere (now in ' + server.bucket + ').\n\nOK applies your move anyway, Cancel reloads the board.')) {
+                    sendMove(item, to, from, server.version);
+                } else {
+                    window.location.reload();
+                }
+            });
+        }
+        if (!response.ok) {
+            // Nothing was applied; show why and resync with the server
+            return response.text().then(msg => {
+                alert('Could not move the task: ' + msg);
+                window.location.reload();
+            });
+        }
+        return response.text().then(html => {
+            // Replace the moved element's HTML with the updated HTML from the server
+            // and re-initialize HTMX on the new element
+            const temp = document.createElement('div');
+            temp.innerHTML = html;
+            const newElem = temp.firstElementChild;
+            if (!newElem) return;
+            item.replaceWith(newElem);
+            if(window.htmx) {
+                window.htmx.process(newElem);
+            }
+        });
+    });
+}
+function saveTitle(el){
+    const li = el.closest('li.task');
+    const title = el.innerText;
+    if (title === el.dataset.saved) return;
+    fetch('/tasks/' + li.dataset.id, {
+        method:'PATCH',
+        headers:{'Content-Type':'application/json', 'X-CSRF-Token': CSRF_TOKEN, 'If-Match': '"' + li.dataset.version + '"'},
+        body: JSON.stringify({title: title})
+    })
+    .then(response => {
+        if (response.status === 412) {
+            return response.json().then(server => {
+                li.dataset.version = server.version;
+                if (server.title === title) return;
+                if (confirm('This task was edited elsewhere.\n\nTheirs: ' + server.title + '\nYours: ' + title + '\n\nOK keeps your text, Cancel takes theirs.')) {
+                    el.dataset.saved = server.title;
+                    saveTitle(el);
+                } else {
+                    el.innerText = server.title;
+                }
+            });
+        }
+        const etag = response.headers.get('ETag');
+        if (response.ok && etag) {
+            li.dataset.version = etag.replace(/"/g, '');
+            el.dataset.saved = title;
+        }
+    });
+}
+    document.addEventListener('DOMContentLoaded', function(){
+            if (READ_ONLY) {
+                document.querySelectorAll('li.task .text').forEach(el => el.contentEditable = 'false');
+            }
+            bootSortable('list-UI', 'UrgentImportant');
+            bootSortable('list-UNI', 'UrgentNotImportant');
+            bootSortable('list-NUI', 'NotUrgentImportant');
+            bootSortable('list-NUN', 'NotUrgentNotImportant');
+            bootSortable('list-TODAY', 'Today');
+});
+
+    // Preserve horizontal scroll position across HTMX reloads
+    let lastScrollX = 0;
+    document.body.addEventListener('htmx:beforeSwap', function() {
+        const ms = document.querySelector('.matrix-scroll');
+        if (ms) lastScrollX = ms.scrollLeft;
+});
+    document.body.addEventListener('htmx:afterSwap', function() {
+        const ms = document.querySelector('.matrix-scroll');
+        if (ms) ms.scrollLeft = lastScrollX;
+});
+    // htmx doesn't swap error responses; show the server's explanation above the board instead
+    document.body.addEventListener('htmx:responseError', function(evt) {
+        const banner = document.getElementById('error-banner');
+        banner.innerHTML = evt.detail.xhr.responseText;
+        banner.hidden = false;
+        clearTimeout(banner.hideTimer);
+        banner.hideTimer = setTimeout(() => banner.hidden = true, 6000);
+});
+    document.getElementById('show-completed-btn').onclick = function() {
+        document.getElementById('completed-panel').style.display = 'block';
+        document.getElementById('completed-tasks-list').dispatchEvent(new Event('revealed'));
+};
+        document.getElementById('refresh-btn').onclick = function() {
+            const ms = document.querySelector('.matrix-scroll');
+            if (ms) {
+                sessionStorage.setItem('matrixScrollX', ms.scrollLeft);
+            }
+            window.location.reload();
+        };
+    ['share', 'webhooks', 'users', 'inbox'].forEach(function(name) {
+        const btn = document.getElementById('show-' + name + '-btn');
+        const panel = document.getElementById(name + '-panel');
+        if (!btn || !panel) return;
+        btn.onclick = function() {
+            panel.style.display = 'block';
+            document.getElementById(name + '-panel-body').dispatchEvent(new Event('revealed'));
+        };
+        panel.querySelector('.close-panel-btn').onclick = function() {
+            panel.style.display = 'none';
+        };
+    });
+    document.getElementById('close-completed-btn').onclick = function() {
+        const ms = document.querySelector('.matrix-scroll');
+        if (ms) {
+            sessionStorage.setItem('matrixScrollX', ms.scrollLeft);
+}
+        document.getElementById('completed-panel').style.display = 'none';
+        window.location.reload();
+};
+    // Restore horizontal scroll position after reload
+    document.addEventListener('DOMContentLoaded', function() {
+        const ms = document.querySelector('.matrix-scroll');
+        const scrollX = sessionStorage.getItem('matrixScrollX');
+        if (ms && scrollX) {
+            ms.scrollLeft = parseInt(scrollX, 10);
+            sessionStorage.removeItem('matrixScrollX');
+}
+});
+// Multi-select: Ctrl/Cmd-click toggles a card, Shift-click extends within a list,
+// and Select mode makes a plain click toggle. Works on open and completed tasks.
+const selected = new Set();
+let selectAnchor = null;
+let selectMode = false;
+function selectable(el){
+    return el.closest('li.task, li.completed-task');
+}
+function setSelected(li, on){
+    li.classList.toggle('selected', on);
+    if (on) selected.add(Number(li.dataset.id)); else selected.delete(Number(li.dataset.id));
+}
+function updateBulkBar(){
+    document.getElementById('bulk-bar').style.display = selected.size ? 'flex' : 'none';
+    document.getElementById('bulk-count').innerText = selected.size + ' selected';
+}
+function clearSelection(){
+    document.querySelectorAll('li.selected').forEach(li => li.classList.remove('selected'));
+    selected.clear();
+    updateBulkBar();
+}
+document.addEventListener('click', function(e){
+    if (READ_ONLY) return;
+    const li = selectable(e.target);
+    if (!li || e.target.closest('button, select, input, a')) return;
+    if (e.shiftKey && selectAnchor && selectAnchor.parentElement === li.parentElement) {
+        const items = Array.from(li.parentElement.children);
+        const [a, b] = [items.indexOf(selectAnchor), items.indexOf(li)].sort((x, y) => x - y);
+        items.slice(a, b + 1).forEach(item => setSelected(item, true));
+    } else if (e.ctrlKey || e.metaKey || selectMode) {
+        setSelected(li, !li.classList.contains('selected'));
+        selectAnchor = li;
+    } else {
+        return;
+    }
+    e.preventDefault();
+    updateBulkBar();
+}, true);
+document.getElementById('select-mode-btn').onclick = function(){
+    selectMode = !selectMode;
+    document.body.classList.toggle('select-mode', selectMode);
+    this.innerText = selectMode ? 'Done selecting' : 'Select';
+    document.querySelectorAll('li.task .text').forEach(el => el.contentEditable = selectMode ? 'false' : 'true');
+    if (!selectMode) clearSelection();
+};
+document.getElementById('bulk-clear').onclick = clearSelection;
+document.querySelectorAll('#bulk-bar [data-bulk]').forEach(function(btn){
+    btn.onclick = function(){
+        const action = btn.dataset.bulk;
+        const payload = { action: action, ids: Array.from(selected) };
+        if (action === 'move') payload.bucket = document.getElementById('bulk-bucket').value;
+        if (action === 'tag') {
+            const words = document.getElementById('bulk-tags').value.split(/\s+/).filter(w => w);
+            payload.addTags = words.filter(w => !w.startsWith('-')).map(w => w.replace(/^\+/, ''));
+            payload.removeTags = words.filter(w => w.startsWith('-')).map(w => w.slice(1));
+        }
+        if (action === 'delete' && !confirm('Delete ' + selected.size + ' task(s)?')) return;
+        fetch('/tasks/bulk', {
+            method: 'POST',
+            headers: {'Content-Type':'application/json', 'X-CSRF-Token': CSRF_TOKEN},
+            body: JSON.stringify(payload)
+        })
+        .then(response => response.ok ? response.json() : response.text().then(msg => { throw new Error(msg); }))
+        .then(data => {
+            const failed = data.results.filter(r => !r.ok);
+            if (failed.length) {
+                alert(failed.length + ' of ' + data.results.length + ' task(s) were skipped:\n' +
+                    failed.map(r => '#' + r.id + ': ' + r.error).join('\n'));
+            }
+        })
+        .catch(err => alert('Bulk ' + action + ' failed: ' + err.message))
+        .finally(() => {
+            const ms = document.querySelector('.matrix-scroll');
+            if (ms) sessionStorage.setItem('matrixScrollX', ms.scrollLeft);
+            window.location.reload();
+        });
+    };
+});
+if (READ_ONLY) document.getElementById('select-mode-btn').style.display = 'none';
+// Keyboard triage. Arrows pick a card across the five columns; see the ? overlay for the rest.
+const COLUMN_IDS = ['list-UI', 'list-UNI', 'list-TODAY', 'list-NUI', 'list-NUN'];
+const QUADRANT_KEYS = { '1': 'list-UI', '2': 'list-UNI', '3': 'list-NUI', '4': 'list-NUN', 't': 'list-TODAY' };
+let kbCard = null;
+function visibleCards(list){
+    return list ? Array.from(list.querySelectorAll('li.task')).filter(li => !li.classList.contains('filtered-out')) : [];
+}
+function focusCard(li){
+    if (kbCard) kbCard.classList.remove('kb-focus');
+    kbCard = li;
+    if (!li) return;
+    li.classList.add('kb-focus');
+    li.scrollIntoView({ block: 'nearest', inline: 'nearest' });
+}
+function focusById(id){
+    const li = document.querySelector('li.task[data-id="' + id + '"]');
+    if (li) focusCard(li);
+}
+// Card to land on once `li` leaves its list: the one below, else the one above
+function neighbourOf(li){
+    const cards = visibleCards(li.parentElement);
+    const i = cards.indexOf(li);
+    return cards[i + 1] || cards[i - 1] || null;
+}
+function moveFocus(dx, dy){
+    if (!kbCard || !document.body.contains(kbCard)) {
+        const first = COLUMN_IDS.map(id => visibleCards(document.getElementById(id))[0]).find(li => li);
+        focusCard(first || null);
+        return;
+    }
+    const col = COLUMN_IDS.indexOf(kbCard.parentElement.id);
+    const row = visibleCards(kbCard.parentElement).indexOf(kbCard);
+    if (dy) {
+        const cards = visibleCards(kbCard.parentElement);
+        focusCard(cards[Math.max(0, Math.min(cards.length - 1, row + dy))]);
+        return;
+    }
+    // Skip empty columns, keeping roughly the same row
+    for (let c = col + dx; c >= 0 && c < COLUMN_IDS.length; c += dx) {
+        const cards = visibleCards(document.getElementById(COLUMN_IDS[c]));
+        if (cards.length) {
+            focusCard(cards[Math.min(row, cards.length - 1)]);
+            return;
+        }
+    }
+}
+function keepFocusAcrossReload(li){
+    const next = li && neighbourOf(li);
+    if (next) sessionStorage.setItem('kbFocus', next.dataset.id);
+    const ms = document.querySelector('.matrix-scroll');
+    if (ms) sessionStorage.setItem('matrixScrollX', ms.scrollLeft);
+}
+function toggleHelp(show){
+    const help = document.getElementById('kb-help');
+    help.style.display = (show === undefined ? help.style.display === 'none' : show) ? 'block' : 'none';
+}
+function applySearch(){
+    const q = document.getElementById('task-search').value.trim().toLowerCase();
+    document.querySelectorAll('li.task').forEach(function(li){
+        const text = li.querySelector('.text').innerText + ' ' + (li.querySelector('.tags') || {}).innerText;
+        li.classList.toggle('filtered-out', q !== '' && !text.toLowerCase().includes(q));
+    });
+    if (kbCard && kbCard.classList.contains('filtered-out')) focusCard(null);
+}
+document.getElementById('task-search').addEventListener('input', applySearch);
+document.getElementById('kb-help-btn').onclick = function(){ toggleHelp(); };
+document.getElementById('kb-help').onclick = function(){ toggleHelp(false); };
+document.addEventListener('DOMContentLoaded', function(){
+    const id = sessionStorage.getItem('kbFocus');
+    if (id) {
+        sessionStorage.removeItem('kbFocus');
+        focusById(id);
+    }
+});
+document.addEventListener('keydown', function(e){
+    const t = e.target;
+    const typing = t.isContentEditable || ['INPUT', 'TEXTAREA', 'SELECT'].includes(t.tagName);
+    if (e.key === 'Escape') {
+        toggleHelp(false);
+        if (t.id === 'task-search') {
+            t.value = '';
+            applySearch();
+        }
+        if (typing) t.blur();
+        return;
+    }
+    if (typing) {
+        // A keyboard edit ends with Enter; Shift+Enter still adds a line break
+        if (e.key === 'Enter' && !e.shiftKey && t.classList.contains('text') && kbCard && kbCard.contains(t)) {
+            e.preventDefault();
+            t.blur();
+        }
+        return;
+    }
+    if (e.ctrlKey || e.metaKey || e.altKey) return;
+    const key = e.key.length === 1 ? e.key.toLowerCase() : e.key;
+    const arrows = { ArrowLeft: [-1, 0], ArrowRight: [1, 0], ArrowUp: [0, -1], ArrowDown: [0, 1] };
+    if (arrows[key]) {
+        moveFocus(arrows[key][0], arrows[key][1]);
+    } else if (key === '?') {
+        toggleHelp();
+    } else if (key === '/') {
+        document.getElementById('task-search').focus();
+    } else if (key === 'n' && !READ_ONLY) {
+        const input = kbCard
+            ? kbCard.parentElement.parentElement.querySelector('.add-form input[name=title]')
+            : document.querySelector('#quick-add input[name=title]');
+        if (input) input.focus();
+    } else if (!kbCard || !document.body.contains(kbCard) || READ_ONLY) {
+        return;
+    } else if (QUADRANT_KEYS[key]) {
+        const to = document.getElementById(QUADRANT_KEYS[key]);
+        const from = kbCard.parentElement;
+        if (to === from) return;
+        const id = kbCard.dataset.id;
+        to.appendChild(kbCard);
+        sendMove(kbCard, to, from, Number(kbCard.dataset.version)).then(() => focusById(id));
+    } else if (key === 'Enter') {
+        const text = kbCard.querySelector('.text');
+        text.focus();
+        getSelection().selectAllChildren(text);
+        getSelection().collapseToEnd();
+    } else if (key === 'x') {
+        keepFocusAcrossReload(kbCard);
+        kbCard.querySelector('.done-btn').click();
+    } else if (key === 'Delete' || key === 'Backspace') {
+        const next = neighbourOf(kbCard);
+        kbCard.querySelector('.delete-btn').click();
+        focusCard(next);
+    } else {
+        return;
+    }
+    e.preventDefault();
+});
+// No custom event listeners needed; Undo button uses hx-on::afterRequest for reload